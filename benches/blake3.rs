@@ -0,0 +1,31 @@
+//! A rough comparison of BLAKE3 in keyed mode against HMAC-SHA256.
+//!
+//! Run with `cargo bench --features blake3`. There's no harness here, just a stopwatch, which is
+//! plenty to show the difference once payloads get big.
+
+use rwt::{Algorithm, Rwt};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const KEY: [u8; 32] = [7; 32];
+const ROUNDS: u32 = 200;
+
+fn time(payload: &str, algorithm: Algorithm) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(Rwt::with_payload_alg(black_box(payload), KEY, algorithm).unwrap());
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    for &size in &[64, 4 << 10, 256 << 10, 1 << 20] {
+        let payload = "x".repeat(size);
+        let hmac = time(&payload, Algorithm::HmacSha256);
+        let blake3 = time(&payload, Algorithm::Blake3Keyed);
+        println!(
+            "{:>8} bytes: hmac-sha256 {:>10?}  blake3 {:>10?}",
+            size, hmac, blake3
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! What sizing the output up front saves `encode`.
+//!
+//! Run with `cargo bench --bench encode`. Writing into an empty `String` is what `encode` used to
+//! do, growing the buffer as it went; `encode` now works out the length first and allocates once.
+//! A counting allocator tallies the allocations per token. As with the others, this is a
+//! stopwatch, not a harness.
+
+use rwt::Rwt;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 100_000;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn time(f: impl Fn() -> String) -> (Duration, f64) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(f());
+    }
+    let elapsed = start.elapsed() / ROUNDS;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    (elapsed, allocations as f64 / f64::from(ROUNDS))
+}
+
+fn main() {
+    for &size in &[16, 256, 4 << 10] {
+        let rwt = Rwt::with_payload("x".repeat(size), "secret").unwrap();
+        let (grown, grown_allocations) = time(|| {
+            let mut buf = String::new();
+            rwt.write_to(&mut buf).unwrap();
+            buf
+        });
+        let (sized, sized_allocations) = time(|| rwt.encode().unwrap());
+        println!(
+            "{:>6} bytes: grown {:>9?} ({:.1} allocations)  sized {:>9?} ({:.1} allocations)",
+            size, grown, grown_allocations, sized, sized_allocations
+        );
+    }
+}
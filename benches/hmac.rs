@@ -0,0 +1,32 @@
+//! What keeping a keyed HMAC context around buys a service signing with one secret.
+//!
+//! Run with `cargo bench --bench hmac`. Signing with the same secret every time hits the
+//! per-thread context; alternating between two secrets re-keys on every token, which is what
+//! every token used to cost. As with the BLAKE3 comparison, this is a stopwatch, not a harness.
+
+use rwt::Rwt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 100_000;
+
+fn time(payload: &str, secrets: [&str; 2]) -> Duration {
+    let start = Instant::now();
+    for round in 0..ROUNDS {
+        let secret = secrets[round as usize % 2];
+        black_box(Rwt::with_payload(black_box(payload), secret).unwrap());
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    for &size in &[16, 256, 4 << 10] {
+        let payload = "x".repeat(size);
+        let same = time(&payload, ["secret", "secret"]);
+        let alternating = time(&payload, ["secret", "other secret"]);
+        println!(
+            "{:>6} bytes: same secret {:>9?}  alternating {:>9?}",
+            size, same, alternating
+        );
+    }
+}
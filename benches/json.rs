@@ -0,0 +1,61 @@
+//! `serde_json` against `simd-json` for reading payloads.
+//!
+//! Run with `cargo bench --features simd-json`. `from_json_str` parses with `simd-json` when the
+//! feature is on; `FromStr` parses with whatever the payload says, which here is `serde_json`.
+//! A stopwatch, like the others.
+
+use rwt::Rwt;
+use serde::{Deserialize, Serialize};
+use std::hint::black_box;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 2_000;
+
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    sub: String,
+    roles: Vec<String>,
+    exp: i64,
+}
+
+impl FromStr for Payload {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+fn time(f: impl Fn()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    for &roles in &[1, 64, 4096] {
+        let payload = Payload {
+            sub: "jdoe".into(),
+            roles: (0..roles).map(|i| format!("role-{}", i)).collect(),
+            exp: 13,
+        };
+        let token = Rwt::with_payload(payload, "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+
+        let serde_json = time(|| {
+            black_box(black_box(&token).parse::<Rwt<Payload>>().unwrap());
+        });
+        let simd_json = time(|| {
+            black_box(Rwt::<Payload>::from_json_str(black_box(&token)).unwrap());
+        });
+        println!(
+            "{:>5} roles: serde_json {:>10?}  simd-json {:>10?}",
+            roles, serde_json, simd_json
+        );
+    }
+}
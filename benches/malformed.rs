@@ -0,0 +1,41 @@
+//! The cost of turning away tokens that were never going to decode.
+//!
+//! Run with `cargo bench --bench malformed`. A bad character up front is caught by the alphabet
+//! check before anything is allocated; one at the very end has to be scanned for, which is about
+//! the worst garbage can do. A good token of the same size is there for comparison. A stopwatch,
+//! like the others.
+
+use rwt::verify_str;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 2_000;
+
+fn time(token: &str) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let _ = black_box(verify_str(black_box(token), "secret"));
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    for &size in &[64, 4 << 10, 256 << 10] {
+        let payload = "x".repeat(size);
+        let token = rwt::Rwt::with_payload(payload, "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+        let (body, signature) = token.split_once('.').unwrap();
+        let early = format!("!{}.{}", &body[1..], signature);
+        let late = format!("{}!.{}", &body[..body.len() - 1], signature);
+
+        println!(
+            "{:>7} bytes: valid {:>10?}  bad first {:>10?}  bad last {:>10?}",
+            size,
+            time(&token),
+            time(&early),
+            time(&late)
+        );
+    }
+}
@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rwt::Rwt;
+
+// Nothing here should panic, whatever the input. Errors are fine; that's the point.
+fuzz_target!(|data: &[u8]| {
+    let _ = Rwt::<String>::parse_bytes(data);
+
+    if let Ok(s) = std::str::from_utf8(data) {
+        let _ = s.parse::<Rwt<String>>();
+        let _ = Rwt::<String>::parse_verified(s, "secret");
+        let _ = Rwt::<String>::parse_url_safe(s);
+        let _ = Rwt::<String>::parse_jwt(s, "secret");
+        let _ = rwt::decode_body(s);
+        let _ = rwt::decode_signature(s);
+        let _ = rwt::verify_str(s, "secret");
+
+        #[allow(deprecated)]
+        let _ = rwt::decode_base64(s);
+    }
+});
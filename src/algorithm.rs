@@ -0,0 +1,426 @@
+use crate::Result;
+use alloc::string::String;
+use hmac::digest::core_api::BlockSizeUser;
+use hmac::digest::Digest;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Sha384, Sha512};
+
+/// The algorithm used to sign a token.
+///
+/// `HmacSha256` is the default, and tokens signed with it are byte-for-byte identical to those
+/// produced by earlier versions of this crate.
+///
+/// HMAC secrets can be any length. One longer than the hash's block size (64 bytes for SHA-256,
+/// 128 for SHA-384 and SHA-512) is hashed down first, as RFC 2104 says, so it signs exactly as
+/// its own digest would. That's what `rust-crypto` did, too: a long key decoded from base64 makes
+/// the same tokens it always has, whichever features are on.
+///
+/// With the `ed25519` feature, tokens may also be signed with an Ed25519 key pair rather than a
+/// shared secret. Such tokens are created and checked with `Rwt::sign_ed25519` and
+/// `Rwt::verify_ed25519`; anything that expects a shared secret rejects them.
+///
+/// With the `blake3` feature, `Blake3Keyed` signs with BLAKE3 in keyed mode, which is a good deal
+/// faster than HMAC for big payloads. Its secret must be exactly 32 bytes.
+///
+/// `None` is for tokens passed between components that trust each other, where a signature is
+/// overhead. It's JWT's `alg: none` with the footgun removed: nothing signs with it, and nothing
+/// that checks a signature accepts it, so a token can't talk its way past `verify` by claiming
+/// to be unsigned. Unsigned tokens are made with `Rwt::with_payload_unsigned` and accepted only
+/// by `Rwt::verify_unsigned`, which you have to go out of your way to call.
+///
+/// Which algorithms there are depends on the features turned on anywhere in the build, so a
+/// `match` outside this crate needs a wildcard arm.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum Algorithm {
+    #[default]
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+    #[cfg(feature = "ed25519")]
+    Ed25519,
+    #[cfg(feature = "blake3")]
+    Blake3Keyed,
+    None,
+}
+
+impl Algorithm {
+    /// The length of a signature, in bytes: the size of the MAC, or of an Ed25519 signature.
+    ///
+    /// An unsigned token's signature is empty.
+    pub const fn signature_len(self) -> usize {
+        match self {
+            Algorithm::HmacSha256 => 32,
+            Algorithm::HmacSha384 => 48,
+            Algorithm::HmacSha512 => 64,
+            #[cfg(feature = "ed25519")]
+            Algorithm::Ed25519 => 64,
+            #[cfg(feature = "blake3")]
+            Algorithm::Blake3Keyed => 32,
+            Algorithm::None => 0,
+        }
+    }
+
+    /// The length of a signature in base64, padded or not: 44 characters for a padded
+    /// HMAC-SHA256, say, or 43 without the `=`.
+    ///
+    /// Every signature made with an algorithm is the same length, so this is a cheap way to turn
+    /// away a token that can't possibly be valid, or to size a buffer. It's the same for the
+    /// URL-safe alphabet as for the standard one.
+    pub const fn signature_b64_len(self, padded: bool) -> usize {
+        let len = self.signature_len();
+        if padded {
+            len.div_ceil(3) * 4
+        } else {
+            (len * 4).div_ceil(3)
+        }
+    }
+
+    /// Sign a byte slice with a shared secret, returning the base64-encoded MAC.
+    ///
+    /// Fails for algorithms that don't use a shared secret, and for `None`, which doesn't sign at
+    /// all.
+    pub(crate) fn sign(self, bytes: &[u8], secret: &[u8]) -> Result<String> {
+        match self {
+            Algorithm::HmacSha256 => Ok(hmac_sha256(bytes, secret)),
+            Algorithm::HmacSha384 => Ok(hmac::<Sha384>(bytes, secret)),
+            Algorithm::HmacSha512 => Ok(hmac::<Sha512>(bytes, secret)),
+            #[cfg(feature = "ed25519")]
+            Algorithm::Ed25519 => Err(crate::Error::Format(
+                "Ed25519 tokens are signed with a key pair, not a shared secret".into(),
+            )),
+            #[cfg(feature = "blake3")]
+            Algorithm::Blake3Keyed => blake3_keyed(bytes, secret),
+            Algorithm::None => Err(crate::Error::Format(
+                "Unsigned tokens have no signature to derive".into(),
+            )),
+        }
+    }
+}
+
+/// An algorithm chosen at the type level, as in `Rwt<T, Hs512>`.
+///
+/// The second parameter of `Rwt` is the algorithm a token type signs and verifies with when not
+/// told otherwise. It defaults to `Hs256`, so a plain `Rwt<T>` is what it always was.
+pub trait SigningAlgorithm {
+    const ALGORITHM: Algorithm;
+}
+
+/// HMAC-SHA256, the default.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Hs256;
+
+/// HMAC-SHA384.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Hs384;
+
+/// HMAC-SHA512.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Hs512;
+
+impl SigningAlgorithm for Hs256 {
+    const ALGORITHM: Algorithm = Algorithm::HmacSha256;
+}
+
+impl SigningAlgorithm for Hs384 {
+    const ALGORITHM: Algorithm = Algorithm::HmacSha384;
+}
+
+impl SigningAlgorithm for Hs512 {
+    const ALGORITHM: Algorithm = Algorithm::HmacSha512;
+}
+
+/// Ed25519, for parsing tokens made by `Rwt::sign_ed25519`.
+///
+/// An Ed25519 token doesn't record its algorithm, so parse it as an `Rwt<T, Ed25519>` for
+/// `verify_ed25519` to accept it. There's no secret to sign or verify with, so `is_valid` and
+/// `with_payload_typed` refuse.
+#[cfg(feature = "ed25519")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Ed25519;
+
+#[cfg(feature = "ed25519")]
+impl SigningAlgorithm for Ed25519 {
+    const ALGORITHM: Algorithm = Algorithm::Ed25519;
+}
+
+#[cfg(feature = "blake3")]
+fn blake3_keyed(bytes: &[u8], secret: &[u8]) -> Result<String> {
+    use core::convert::TryInto;
+
+    let key: &[u8; 32] = secret.try_into().map_err(|_| {
+        crate::Error::Format(alloc::format!(
+            "BLAKE3 keys are 32 bytes, not {}",
+            secret.len()
+        ))
+    })?;
+    Ok(base64::encode(blake3::keyed_hash(key, bytes).as_bytes()))
+}
+
+/// HMAC-SHA256, keyed once per thread and secret.
+///
+/// Keying an HMAC hashes a block of padded key before a byte of the message, which is most of the
+/// work for a small token. Services tend to sign everything with the one secret, so each thread
+/// keeps the last context it keyed and, given the same secret again, picks up from a copy of it
+/// rather than starting over. A different secret gets a fresh context. Either way, the MAC is
+/// the same.
+///
+/// The secret it's compared against is kept as a `SecretKey`, so it's wiped when a different
+/// secret replaces it or the thread exits, as any other `SecretKey` would be.
+///
+/// With `zeroize`, nothing is kept: a secret lying around in thread-local storage is just what
+/// that feature is there to prevent.
+#[cfg(all(feature = "std", not(feature = "zeroize")))]
+fn hmac_sha256(bytes: &[u8], secret: &[u8]) -> String {
+    use crate::SecretKey;
+    use core::cell::RefCell;
+    use hmac::{Hmac, Mac};
+    use subtle::ConstantTimeEq;
+
+    thread_local! {
+        static CONTEXT: RefCell<Option<(SecretKey, Hmac<Sha256>)>> = const { RefCell::new(None) };
+    }
+
+    let sign = |context: &RefCell<Option<(SecretKey, Hmac<Sha256>)>>| {
+        let mut context = context.borrow_mut();
+        let mut mac = match &mut *context {
+            Some((key, mac)) if bool::from(key.as_ref().ct_eq(secret)) => mac.clone(),
+            context => {
+                let mac = <Hmac<Sha256> as Mac>::new_from_slice(secret)
+                    .expect("HMAC accepts keys of any length");
+                context.insert((SecretKey::new(secret), mac)).1.clone()
+            }
+        };
+        mac.update(bytes);
+        base64::encode(mac.finalize().into_bytes())
+    };
+
+    // The context is gone once the thread starts tearing down; sign the slow way.
+    CONTEXT
+        .try_with(sign)
+        .unwrap_or_else(|_| hmac::<Sha256>(bytes, secret))
+}
+
+#[cfg(not(all(feature = "std", not(feature = "zeroize"))))]
+fn hmac_sha256(bytes: &[u8], secret: &[u8]) -> String {
+    hmac::<Sha256>(bytes, secret)
+}
+
+#[cfg(not(feature = "zeroize"))]
+pub(crate) fn hmac<D: Digest + BlockSizeUser>(bytes: &[u8], secret: &[u8]) -> String {
+    use hmac::{Mac, SimpleHmac};
+
+    let mut mac =
+        <SimpleHmac<D> as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(bytes);
+    base64::encode(mac.finalize().into_bytes())
+}
+
+/// HMAC, computed by hand so that every copy of the key material is wiped once we're done.
+///
+/// The `hmac` crate keeps its padded key blocks in buffers we can't get at, so with the `zeroize`
+/// feature we build them ourselves inside `Zeroizing` buffers instead. The result is identical.
+#[cfg(feature = "zeroize")]
+pub(crate) fn hmac<D: Digest + BlockSizeUser>(bytes: &[u8], secret: &[u8]) -> String {
+    use alloc::{vec, vec::Vec};
+    use hmac::digest::generic_array::GenericArray;
+    use zeroize::Zeroizing;
+
+    // Keys longer than a block are hashed first; either way, the key is zero-padded to a block.
+    let mut key = Zeroizing::new(vec![0u8; D::block_size()]);
+    if secret.len() > key.len() {
+        let digest = GenericArray::from_mut_slice(&mut key[..<D as Digest>::output_size()]);
+        D::new().chain_update(secret).finalize_into(digest);
+    } else {
+        key[..secret.len()].copy_from_slice(secret);
+    }
+
+    let mut pad = Zeroizing::new(key.iter().map(|k| k ^ 0x36).collect::<Vec<_>>());
+    let inner = D::new().chain_update(&*pad).chain_update(bytes).finalize();
+
+    pad.iter_mut()
+        .zip(key.iter())
+        .for_each(|(p, k)| *p = k ^ 0x5c);
+    let outer = D::new().chain_update(&*pad).chain_update(inner).finalize();
+    base64::encode(outer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Algorithm;
+
+    const PAYLOAD: &[u8] = br#"{"jti":"this one","exp":13}"#;
+
+    #[test]
+    fn sign_with_short_secret() {
+        assert_eq!(
+            "Ir9W3KCkyGNmsPFURs4Sj7aQSkuvcqpQ7kTk4F6wCyU=",
+            Algorithm::HmacSha256.sign(PAYLOAD, b"secret").unwrap()
+        );
+        assert_eq!(
+            "V4Xuje80EuUZgwGIX8IPrezFqlpSBB+dKCZw18Ys6yzzqh2zvwV8QTbqOm/CO/Xt",
+            Algorithm::HmacSha384.sign(PAYLOAD, b"secret").unwrap()
+        );
+        assert_eq!(
+            "pIW9O2NthBVwyg9GrK/Ii1j61JosrDR92grr9MeP9q8OuF3TJ1eAQ6PO7pC6t3auV8u5oUVW6ORQV5hvHfaM3Q==",
+            Algorithm::HmacSha512.sign(PAYLOAD, b"secret").unwrap()
+        );
+    }
+
+    #[test]
+    fn sign_with_long_secret() {
+        let secret = [b'k'; 200];
+        assert_eq!(
+            "hNxEIP936LxsaKZVlCn0ysFHVFhdA/s7qsbF4OhdjG4=",
+            Algorithm::HmacSha256.sign(PAYLOAD, &secret).unwrap()
+        );
+        assert_eq!(
+            "RaNjIM7IBglkpo0d6Ca06EoFkGOrL31+X1QGSCJSZGMEN7aQFP2e2FAjdH2/MCcX",
+            Algorithm::HmacSha384.sign(PAYLOAD, &secret).unwrap()
+        );
+        assert_eq!(
+            "6fdPA38Xyy9vu5YXmCLbPiS3bVKEeudovjk11U4jkYaZc7duUyos3wyPjARtle5IuGGsCuqsPmPlPazJbt4fEw==",
+            Algorithm::HmacSha512.sign(PAYLOAD, &secret).unwrap()
+        );
+    }
+
+    #[test]
+    fn signature_lengths() {
+        use crate::Rwt;
+
+        fn check(rwt: Rwt<&str>) {
+            let alg = rwt.header().alg;
+            let bytes = rwt.signature_bytes().unwrap();
+            let unpadded = base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD);
+            assert_eq!(alg.signature_len(), bytes.len(), "{:?}", alg);
+            assert_eq!(
+                alg.signature_b64_len(true),
+                rwt.signature().len(),
+                "{:?}",
+                alg
+            );
+            assert_eq!(alg.signature_b64_len(false), unpadded.len(), "{:?}", alg);
+        }
+
+        for &alg in &[
+            Algorithm::HmacSha256,
+            Algorithm::HmacSha384,
+            Algorithm::HmacSha512,
+        ] {
+            check(Rwt::with_payload_alg("payload", "secret", alg).unwrap());
+        }
+        check(Rwt::with_payload_unsigned("payload"));
+        #[cfg(feature = "ed25519")]
+        {
+            let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+            check(Rwt::sign_ed25519("payload", &key).unwrap());
+        }
+        #[cfg(feature = "blake3")]
+        check(Rwt::with_payload_alg("payload", [7; 32], Algorithm::Blake3Keyed).unwrap());
+
+        assert_eq!(44, Algorithm::HmacSha256.signature_b64_len(true));
+        assert_eq!(43, Algorithm::HmacSha256.signature_b64_len(false));
+    }
+
+    #[test]
+    fn pooled_context_matches_fresh_one() {
+        use super::{hmac, hmac_sha256};
+        use sha2::Sha256;
+
+        // Alternating secrets, including one that starts like another, must re-key every time.
+        let long = [b'k'; 200];
+        let secrets: [&[u8]; 6] = [b"secret", b"secret", b"secre", b"secret2", &long, b""];
+        for _ in 0..2 {
+            for secret in &secrets {
+                assert_eq!(
+                    hmac::<Sha256>(PAYLOAD, secret),
+                    hmac_sha256(PAYLOAD, secret)
+                );
+                assert_eq!(hmac::<Sha256>(b"", secret), hmac_sha256(b"", secret));
+            }
+        }
+
+        let expected = hmac::<Sha256>(PAYLOAD, b"secret");
+        let threads: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| hmac_sha256(PAYLOAD, b"secret")))
+            .collect();
+        for thread in threads {
+            assert_eq!(expected, thread.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn sign_with_block_size_secrets() {
+        use sha2::{Digest, Sha256, Sha384, Sha512};
+
+        fn secret(len: usize) -> Vec<u8> {
+            (0..len).map(|i| (i % 251) as u8).collect()
+        }
+
+        let cases = [
+            (Algorithm::HmacSha256, 64, "DJc+3g3DxGDAFRouoExVfEk6rrVMi+AY7VAhpcxcQ+Y="),
+            (Algorithm::HmacSha256, 65, "KvjyOLABx/w7pzYK5kXS1bpjdZV9UGwXc6bNv+tHUeQ="),
+            (Algorithm::HmacSha256, 4096, "6A3+NttdUVjnakM+A1mR9hwuRgpK8euWtT1u6W/gCmw="),
+            (Algorithm::HmacSha384, 128, "v1bEFisxx50NRTy085gla3M3H4av2lAUjbQbEbJVa2B5aVxQd0MGxYwrPm4aSsdM"),
+            (Algorithm::HmacSha384, 129, "Ca0yCK6lGBjoVHsxHTrXzPZqPizpg10BsxJor6tmKXRb4HM1Yu7dhgXhQoreIZwE"),
+            (Algorithm::HmacSha384, 4096, "Y3YXqSsmDwE+lQ6gTslDoewA+ZIplOA0/6KC6aQvqw9k5YcCWdaO/2WqzCXsLhCY"),
+            (Algorithm::HmacSha512, 128, "DomVDsiSBmllBdmNFbze3KNE7YWeVwV5dLCTPCP0rYiZCXwaMrjhrqZW4bPIHEP05TOpUjwOCamFGafWZsQvXA=="),
+            (Algorithm::HmacSha512, 129, "nVE5+Ub42VhI2/1ab2KRcicp2WHlx0ThFfIkw+3vo4YkuazJnCpVaU9B6BCi56HItGW7mJwzKt5X3AtHQa5fmw=="),
+            (Algorithm::HmacSha512, 4096, "nmQi+xdrLB4KNXz5nZyJZ2yu7aSRWjSDB8iAVQMKS43OdVl6H9/AcnIuELAtW0iNhnKzp+UtHDiaL3PQlZKR0g=="),
+        ];
+        for &(algorithm, len, expected) in &cases {
+            assert_eq!(
+                expected,
+                algorithm.sign(PAYLOAD, &secret(len)).unwrap(),
+                "{}",
+                len
+            );
+        }
+
+        // One byte over the block, a key signs as its digest.
+        let long = secret(65);
+        let digest = Sha256::digest(&long);
+        assert_eq!(
+            Algorithm::HmacSha256.sign(PAYLOAD, &long).unwrap(),
+            Algorithm::HmacSha256.sign(PAYLOAD, &digest).unwrap()
+        );
+        let long = secret(129);
+        let digest = Sha384::digest(&long);
+        assert_eq!(
+            Algorithm::HmacSha384.sign(PAYLOAD, &long).unwrap(),
+            Algorithm::HmacSha384.sign(PAYLOAD, &digest).unwrap()
+        );
+        let digest = Sha512::digest(&long);
+        assert_eq!(
+            Algorithm::HmacSha512.sign(PAYLOAD, &long).unwrap(),
+            Algorithm::HmacSha512.sign(PAYLOAD, &digest).unwrap()
+        );
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_keyed_round_trip() {
+        let rwt = crate::Rwt::with_payload_alg("payload", [7; 32], Algorithm::Blake3Keyed).unwrap();
+        assert_eq!(32, rwt.signature_bytes().unwrap().len());
+        assert!(rwt.verify_with_alg([7; 32], Algorithm::Blake3Keyed).is_ok());
+        assert!(rwt
+            .verify_with_alg([8; 32], Algorithm::Blake3Keyed)
+            .is_err());
+
+        // There's no type for BLAKE3, so a plain `is_valid` expects HMAC-SHA256 and says no.
+        assert!(!rwt.is_valid([7; 32]));
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_keyed_requires_32_byte_key() {
+        for len in &[0, 31, 33] {
+            match Algorithm::Blake3Keyed.sign(PAYLOAD, &vec![7; *len]) {
+                Err(crate::Error::Format(_)) => (),
+                other => panic!("expected Format error, got {:?}", other),
+            }
+        }
+    }
+}
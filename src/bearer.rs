@@ -0,0 +1,56 @@
+use crate::{Error, Result, Rwt, Signable};
+use alloc::format;
+use http::header::{HeaderName, HeaderValue, AUTHORIZATION};
+
+impl<T: Signable, A> Rwt<T, A> {
+    /// Encode the token as an `Authorization: Bearer` header, ready to insert into a request.
+    ///
+    /// Errors are those of `encode`.
+    pub fn bearer_header(&self) -> Result<(HeaderName, HeaderValue)> {
+        let value = format!("Bearer {}", self.encode()?);
+        let value = HeaderValue::from_str(&value)
+            .map_err(|e| Error::Format(format!("Invalid header value: {}", e)))?;
+        Ok((AUTHORIZATION, value))
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{Error, Rwt};
+    use http::header::AUTHORIZATION;
+    use serde::{Serialize, Serializer};
+
+    #[test]
+    fn bearer_header() {
+        let rwt = Rwt::with_payload("payload", "secret").unwrap();
+        let (name, value) = rwt.bearer_header().unwrap();
+        assert_eq!(AUTHORIZATION, name);
+
+        let value = value.to_str().unwrap();
+        assert!(value.starts_with("Bearer "));
+        assert_eq!(rwt.encode().unwrap(), &value[7..]);
+    }
+
+    #[test]
+    fn bearer_header_with_unserializable_payload() {
+        struct Unserializable;
+
+        impl Serialize for Unserializable {
+            fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("nope"))
+            }
+        }
+
+        let rwt: Rwt<_> = Rwt {
+            payload: Unserializable,
+            signature: String::new(),
+            header: Default::default(),
+            format: Default::default(),
+            marker: Default::default(),
+        };
+        match rwt.bearer_header() {
+            Err(Error::Json(_)) => (),
+            other => panic!("expected Json error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
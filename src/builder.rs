@@ -0,0 +1,577 @@
+use crate::{
+    Algorithm, Error, Expiring, Format, Header, IssuedAt, IssuedAtMut, NotBefore, Result, Rwt,
+    Signable,
+};
+#[cfg(feature = "std")]
+use crate::{Clock, ExpiringMut, SystemClock};
+use alloc::borrow::ToOwned;
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use core::fmt;
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+/// A timestamp (or what it's computed from) to be written into the payload at signing time, along
+/// with the setter to write it.
+type Stamp<T, V = i64> = (V, fn(&mut T, i64));
+
+/// The builder's clock, which has nothing to show for itself in `Debug`.
+#[cfg(feature = "std")]
+struct BuilderClock(Box<dyn Clock>);
+
+#[cfg(feature = "std")]
+impl fmt::Debug for BuilderClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+/// Assembles a token one option at a time.
+///
+/// `Rwt::with_payload` remains the shortcut for the common case. The builder exists for when you
+/// want something other than the defaults:
+///
+/// ```
+/// # use rwt::{Algorithm, Rwt};
+/// let token = Rwt::builder()
+///     .payload("my payload")
+///     .algorithm(Algorithm::HmacSha512)
+///     .key_id("2020-05")
+///     .sign("secret")
+///     .unwrap();
+///
+/// assert!(token.verify_with_alg("secret", Algorithm::HmacSha512).is_ok());
+/// ```
+#[derive(Debug)]
+pub struct RwtBuilder<T> {
+    payload: Option<T>,
+    algorithm: Algorithm,
+    key_id: Option<String>,
+    format: Format,
+    issued_at: Option<Stamp<T>>,
+    checks: Vec<fn(&T) -> Result<()>>,
+    check_claims: bool,
+    #[cfg(feature = "std")]
+    expires_in: Option<Stamp<T, Duration>>,
+    #[cfg(feature = "std")]
+    clock: BuilderClock,
+}
+
+impl<T> RwtBuilder<T> {
+    pub fn new() -> Self {
+        RwtBuilder {
+            payload: None,
+            algorithm: Algorithm::default(),
+            key_id: None,
+            format: Format::Json,
+            issued_at: None,
+            checks: Vec::new(),
+            check_claims: true,
+            #[cfg(feature = "std")]
+            expires_in: None,
+            #[cfg(feature = "std")]
+            clock: BuilderClock(Box::new(SystemClock)),
+        }
+    }
+
+    /// Set the payload. This is the only required option.
+    pub fn payload(mut self, payload: T) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    /// Set the signing algorithm. Defaults to `Algorithm::HmacSha256`.
+    ///
+    /// The token is still an `Rwt<T>`, which `is_valid` checks with HMAC-SHA256, so check a token
+    /// signed any other way with `verify_with_alg`.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Set a key id identifying the secret used to sign the token.
+    ///
+    /// The key id is covered by the signature. It can't be empty; `sign` fails with
+    /// `Error::Format` if it is.
+    pub fn key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    /// Set the payload's serialization format. Defaults to `Format::Json`.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sign the payload whatever its time claims say.
+    ///
+    /// By default, `sign` refuses a payload whose standard claims describe a token that could
+    /// never be used: one whose `nbf` is later than its `exp`, or whose `exp` is earlier than its
+    /// `iat`. This turns that off, for payloads that use those names to mean something else.
+    /// Checks asked for with `check_not_before` or `check_issued_at` still run.
+    pub fn unchecked(mut self) -> Self {
+        self.check_claims = false;
+        self
+    }
+}
+
+impl<T: IssuedAtMut> RwtBuilder<T> {
+    /// Stamp the payload's issued-at time with `now` when the token is signed.
+    ///
+    /// As elsewhere in this crate, `now` is a Unix timestamp in seconds supplied by the caller.
+    pub fn issued_at(mut self, now: i64) -> Self {
+        self.issued_at = Some((now, T::set_issued_at));
+        self
+    }
+}
+
+impl<T: Expiring + NotBefore> RwtBuilder<T> {
+    /// Refuse to sign a payload whose `nbf` is later than its `exp`.
+    ///
+    /// Such a token would never be valid at all. The check fails with `Error::Format`. `sign`
+    /// already checks the standard `nbf` and `exp` claims unless told not to; this goes through
+    /// `NotBefore` and `Expiring`, for payloads that keep those times under other names.
+    pub fn check_not_before(mut self) -> Self {
+        self.checks.push(check_not_before::<T>);
+        self
+    }
+}
+
+impl<T: Expiring + IssuedAt> RwtBuilder<T> {
+    /// Refuse to sign a payload whose `exp` is earlier than its `iat`.
+    ///
+    /// Such a token would be dead on arrival. The check fails with `Error::Format`, and it sees
+    /// the payload after `issued_at` and `expires_in` have been applied. As with
+    /// `check_not_before`, this is for payloads whose times aren't the standard claims, which
+    /// `sign` checks anyway.
+    pub fn check_issued_at(mut self) -> Self {
+        self.checks.push(check_issued_at::<T>);
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> RwtBuilder<T> {
+    /// Set the clock `expires_in` counts from. Defaults to `SystemClock`.
+    ///
+    /// This is the same `Clock` the time-based checks on `Rwt` take, so swap in a `FixedClock`
+    /// for tests.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = BuilderClock(Box::new(clock));
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: ExpiringMut> RwtBuilder<T> {
+    /// Stamp the payload's expiry with the current time plus `ttl` when the token is signed.
+    ///
+    /// Fractions of a second are dropped, and an expiry past the end of `i64` is clamped to
+    /// `i64::MAX`. The current time comes from the builder's clock; see `clock`.
+    pub fn expires_in(mut self, ttl: Duration) -> Self {
+        self.expires_in = Some((ttl, T::set_expiry));
+        self
+    }
+}
+
+impl<T: Signable> RwtBuilder<T> {
+    /// Sign the token, consuming the builder.
+    ///
+    /// Returns `Error::Format` if no payload was provided, or if the payload's time claims would
+    /// make a token that's dead on arrival: an `nbf` after its `exp`, or an `exp` before its
+    /// `iat`. The claims are read from the serialized payload under their standard names, so a
+    /// payload without them isn't checked; see `unchecked` to skip this. The checks requested
+    /// with `check_not_before` or `check_issued_at` run, too.
+    pub fn sign<S: AsRef<[u8]>>(self, secret: S) -> Result<Rwt<T>> {
+        let mut payload = self
+            .payload
+            .ok_or_else(|| Error::Format("Missing payload".to_owned()))?;
+        if let Some((now, set_issued_at)) = self.issued_at {
+            set_issued_at(&mut payload, now);
+        }
+        #[cfg(feature = "std")]
+        {
+            if let Some((ttl, set_expiry)) = self.expires_in {
+                let ttl = i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX);
+                set_expiry(&mut payload, self.clock.0.now().saturating_add(ttl));
+            }
+        }
+        for check in &self.checks {
+            check(&payload)?;
+        }
+        let body = self.format.serialize(&payload)?;
+        if self.check_claims {
+            check_claims(&body, self.format)?;
+        }
+        let header = Header::new(self.algorithm, self.key_id);
+        Rwt::sign_body(payload, &body, header, self.format, secret.as_ref())
+    }
+}
+
+/// Check the standard time claims of a serialized payload, if it has any.
+///
+/// A body that isn't a map, or whose claims aren't integers, doesn't have standard claims, and
+/// passes.
+#[cfg(feature = "serde")]
+fn check_claims(body: &[u8], format: Format) -> Result<()> {
+    #[derive(serde::Deserialize)]
+    struct Times {
+        nbf: Option<i64>,
+        iat: Option<i64>,
+        exp: Option<i64>,
+    }
+
+    let times: Option<Times> = match format {
+        #[cfg(feature = "msgpack")]
+        Format::MessagePack => rmp_serde::from_slice(body).ok(),
+        _ => serde_json::from_slice(body).ok(),
+    };
+    if let Some(Times {
+        nbf,
+        iat,
+        exp: Some(exp),
+    }) = times
+    {
+        if let Some(nbf) = nbf {
+            check_window(nbf, exp)?;
+        }
+        if let Some(iat) = iat {
+            check_lifetime(iat, exp)?;
+        }
+    }
+    Ok(())
+}
+
+/// Without serde, a payload is only bytes and has no claims to check.
+#[cfg(not(feature = "serde"))]
+fn check_claims(_body: &[u8], _format: Format) -> Result<()> {
+    Ok(())
+}
+
+fn check_not_before<T: Expiring + NotBefore>(payload: &T) -> Result<()> {
+    check_window(payload.not_before(), payload.expiry())
+}
+
+fn check_issued_at<T: Expiring + IssuedAt>(payload: &T) -> Result<()> {
+    check_lifetime(payload.issued_at(), payload.expiry())
+}
+
+fn check_window(nbf: i64, exp: i64) -> Result<()> {
+    if nbf > exp {
+        return Err(Error::Format(format!(
+            "Token would never be valid: nbf ({}) is after exp ({})",
+            nbf, exp
+        )));
+    }
+    Ok(())
+}
+
+fn check_lifetime(iat: i64, exp: i64) -> Result<()> {
+    if exp < iat {
+        return Err(Error::Format(format!(
+            "Token would be expired on issue: exp ({}) is before iat ({})",
+            exp, iat
+        )));
+    }
+    Ok(())
+}
+
+impl<T> Default for RwtBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{Error, Expiring, IssuedAt, IssuedAtMut, NotBefore, Rwt};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        jti: String,
+    }
+
+    #[derive(Serialize)]
+    struct Issued {
+        iat: i64,
+    }
+
+    impl IssuedAt for Issued {
+        fn issued_at(&self) -> i64 {
+            self.iat
+        }
+    }
+
+    impl IssuedAtMut for Issued {
+        fn set_issued_at(&mut self, iat: i64) {
+            self.iat = iat;
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Window {
+        nbf: i64,
+        iat: i64,
+        exp: i64,
+    }
+
+    impl Expiring for Window {
+        fn expiry(&self) -> i64 {
+            self.exp
+        }
+    }
+
+    impl NotBefore for Window {
+        fn not_before(&self) -> i64 {
+            self.nbf
+        }
+    }
+
+    impl IssuedAt for Window {
+        fn issued_at(&self) -> i64 {
+            self.iat
+        }
+    }
+
+    impl IssuedAtMut for Window {
+        fn set_issued_at(&mut self, iat: i64) {
+            self.iat = iat;
+        }
+    }
+
+    fn sign_window(nbf: i64, iat: i64, exp: i64) -> crate::Result<Rwt<Window>> {
+        Rwt::builder()
+            .payload(Window { nbf, iat, exp })
+            .check_not_before()
+            .check_issued_at()
+            .sign("secret")
+    }
+
+    #[test]
+    fn consistent_times_are_signed() {
+        assert!(sign_window(100, 100, 200).is_ok());
+        assert!(sign_window(200, 100, 200).is_ok());
+    }
+
+    #[test]
+    fn nbf_after_exp_is_rejected() {
+        match sign_window(201, 100, 200) {
+            Err(Error::Format(message)) => assert!(message.contains("nbf")),
+            other => panic!("expected Format error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn exp_before_iat_is_rejected() {
+        match sign_window(0, 201, 200) {
+            Err(Error::Format(message)) => assert!(message.contains("iat")),
+            other => panic!("expected Format error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn checks_see_stamped_times() {
+        let result = Rwt::builder()
+            .payload(Window {
+                nbf: 0,
+                iat: 0,
+                exp: 200,
+            })
+            .issued_at(300)
+            .check_issued_at()
+            .sign("secret");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn inconsistent_times_are_rejected_by_default() {
+        let sign = |nbf, iat, exp| {
+            Rwt::builder()
+                .payload(Window { nbf, iat, exp })
+                .sign("secret")
+        };
+        assert!(sign(100, 100, 200).is_ok());
+        match sign(201, 100, 200) {
+            Err(Error::Format(message)) => assert!(message.contains("nbf")),
+            other => panic!("expected Format error, got {:?}", other.map(|_| ())),
+        }
+        match sign(0, 201, 200) {
+            Err(Error::Format(message)) => assert!(message.contains("iat")),
+            other => panic!("expected Format error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_times_are_checked() {
+        let rwt = Rwt::builder()
+            .payload(Window {
+                nbf: 201,
+                iat: 100,
+                exp: 200,
+            })
+            .format(crate::Format::MessagePack)
+            .sign("secret");
+        assert!(rwt.is_err());
+    }
+
+    #[test]
+    fn unchecked_skips_the_default_check() {
+        let window = || Window {
+            nbf: 300,
+            iat: 300,
+            exp: 200,
+        };
+        let rwt = Rwt::builder().payload(window()).unchecked().sign("secret");
+        assert!(rwt.is_ok());
+
+        let rwt = Rwt::builder()
+            .payload(window())
+            .unchecked()
+            .check_not_before()
+            .sign("secret");
+        assert!(rwt.is_err());
+    }
+
+    #[test]
+    fn payloads_without_standard_claims_are_signed() {
+        #[derive(Serialize)]
+        struct Odd {
+            exp: &'static str,
+            nbf: i64,
+        }
+
+        assert!(Rwt::builder().payload("payload").sign("secret").is_ok());
+        assert!(Rwt::builder()
+            .payload(Odd {
+                exp: "soon",
+                nbf: 300
+            })
+            .sign("secret")
+            .is_ok());
+    }
+
+    #[test]
+    fn build_rwt_with_key_id() {
+        let rwt = Rwt::builder()
+            .payload(Payload {
+                jti: "this one".to_owned(),
+            })
+            .key_id("2020-05")
+            .sign("secret")
+            .unwrap();
+
+        assert!(rwt.is_valid("secret"));
+        assert!(!rwt.is_valid("other secret"));
+
+        let json = serde_json::to_string(&rwt).unwrap();
+        let rwt2: Rwt<Payload> = serde_json::from_str(&json).unwrap();
+        assert_eq!(rwt, rwt2);
+        assert!(rwt2.is_valid("secret"));
+    }
+
+    #[test]
+    fn key_id_is_signed() {
+        let payload = Payload {
+            jti: "this one".to_owned(),
+        };
+        let with_kid = Rwt::builder()
+            .payload(payload)
+            .key_id("2020-05")
+            .sign("secret")
+            .unwrap();
+        let without_kid = Rwt::with_payload(with_kid.payload, "secret").unwrap();
+        assert_ne!(with_kid.signature, without_kid.signature);
+    }
+
+    #[test]
+    fn sign_without_payload() {
+        match Rwt::<Payload>::builder().sign("secret") {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stamp_issued_at() {
+        let rwt = Rwt::builder()
+            .payload(Issued { iat: 0 })
+            .issued_at(100)
+            .sign("secret")
+            .unwrap();
+
+        assert_eq!(100, rwt.payload.iat);
+        assert!(rwt.is_valid("secret"));
+        assert!(rwt.older_than(161, 60));
+    }
+
+    #[cfg(feature = "std")]
+    mod expires_in {
+        use crate::{Expiring, ExpiringMut, FixedClock, Rwt};
+        use serde::Serialize;
+        use std::time::Duration;
+
+        #[derive(Serialize)]
+        struct Expires {
+            exp: i64,
+        }
+
+        impl Expiring for Expires {
+            fn expiry(&self) -> i64 {
+                self.exp
+            }
+        }
+
+        impl ExpiringMut for Expires {
+            fn set_expiry(&mut self, exp: i64) {
+                self.exp = exp;
+            }
+        }
+
+        fn expires_in(ttl: Duration) -> i64 {
+            let rwt = Rwt::builder()
+                .payload(Expires { exp: 0 })
+                .clock(FixedClock(1_000))
+                .expires_in(ttl)
+                .sign("secret")
+                .unwrap();
+            assert!(rwt.is_valid("secret"));
+            rwt.payload.exp
+        }
+
+        #[test]
+        fn stamp_expires_in() {
+            assert_eq!(1_900, expires_in(Duration::from_secs(900)));
+        }
+
+        #[test]
+        fn expires_in_rounds_down() {
+            assert_eq!(1_000, expires_in(Duration::from_millis(999)));
+            assert_eq!(1_001, expires_in(Duration::from_millis(1_999)));
+        }
+
+        #[test]
+        fn expires_in_saturates() {
+            assert_eq!(i64::MAX, expires_in(Duration::from_secs(i64::MAX as u64)));
+            assert_eq!(i64::MAX, expires_in(Duration::from_secs(u64::MAX)));
+        }
+
+        #[test]
+        fn expires_in_uses_system_clock_by_default() {
+            let rwt = Rwt::builder()
+                .payload(Expires { exp: 0 })
+                .expires_in(Duration::from_secs(60))
+                .sign("secret")
+                .unwrap();
+            assert!(rwt.payload.exp > 1_600_000_000);
+        }
+    }
+}
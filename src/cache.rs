@@ -0,0 +1,300 @@
+use crate::{Clock, Expiring, Result, Rwt, SecretKey};
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::fmt::Debug;
+use core::str::FromStr;
+use subtle::ConstantTimeEq;
+
+/// A memo of tokens that have already been verified, for services that see the same one over and
+/// over.
+///
+/// Entries are keyed by the encoded token and hold its parsed payload, so a hit costs a lookup
+/// rather than an HMAC and a parse. The cache remembers the one secret its entries were verified
+/// against, and a lookup with any other secret is treated as a miss: the cache never vouches for
+/// a token on someone else's behalf. A token that verifies under a new secret empties the cache
+/// and starts it over with that secret, so keep one cache per secret if you use several.
+///
+/// Expired tokens are never served, and never cached in the first place. Expired entries are
+/// dropped as they're found; call `evict_expired` now and then to sweep up the ones nobody asks
+/// for. The cache holds at most `capacity` tokens. Once it's full, a new token makes room by
+/// sweeping out the expired ones, and failing that, the one closest to expiry.
+///
+/// The time comes from the clock the cache was created with, as with `Rwt::is_expired`.
+#[derive(Debug)]
+pub struct TokenCache<T, C> {
+    entries: BTreeMap<String, T>,
+    secret: Option<SecretKey>,
+    capacity: usize,
+    clock: C,
+}
+
+impl<T, C: Clock> TokenCache<T, C> {
+    /// The number of tokens a cache made with `new` will hold.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    pub fn new(clock: C) -> TokenCache<T, C> {
+        TokenCache::with_capacity(clock, Self::DEFAULT_CAPACITY)
+    }
+
+    /// Create a cache holding at most `capacity` tokens.
+    ///
+    /// The payload `get_verified` returns lives in the cache, so a capacity of zero is taken as
+    /// one.
+    pub fn with_capacity(clock: C, capacity: usize) -> TokenCache<T, C> {
+        TokenCache {
+            entries: BTreeMap::new(),
+            secret: None,
+            capacity: capacity.max(1),
+            clock,
+        }
+    }
+
+    /// The number of tokens in the cache, expired or not.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<T: Expiring, C: Clock> TokenCache<T, C> {
+    /// Drop every entry whose token has expired.
+    pub fn evict_expired(&mut self) {
+        let now = self.clock.now();
+        self.entries.retain(|_, payload| payload.expiry() >= now);
+    }
+
+    /// Make room for one more entry, if there isn't any.
+    fn make_room(&mut self) {
+        if self.entries.len() < self.capacity {
+            return;
+        }
+        self.evict_expired();
+        while self.entries.len() >= self.capacity {
+            let soonest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, payload)| payload.expiry())
+                .map(|(token, _)| token.clone());
+            match soonest {
+                Some(token) => self.entries.remove(&token),
+                None => break,
+            };
+        }
+    }
+}
+
+impl<T, E, C> TokenCache<T, C>
+where
+    E: Debug,
+    T: FromStr<Err = E> + Expiring,
+    C: Clock,
+{
+    /// The payload of a verified, unexpired token.
+    ///
+    /// A token verified before (against the same secret) comes straight from the cache. Anything
+    /// else goes through `Rwt::parse_verified` and `check_expiry`, and is cached only if it
+    /// passes both. That includes a token which has expired since it was cached: the entry is
+    /// dropped and the token checked again, so errors are always theirs, `Error::InvalidSignature`
+    /// for a forgery and `Error::Expired` for a stale token.
+    pub fn get_verified<S: AsRef<[u8]>>(&mut self, token: &str, secret: S) -> Result<&T> {
+        let now = self.clock.now();
+        let secret = secret.as_ref();
+        let same_secret = match &self.secret {
+            Some(cached) => bool::from(cached.as_ref().ct_eq(secret)),
+            None => false,
+        };
+
+        let hit = match self.entries.get(token) {
+            None => false,
+            Some(payload) if payload.expiry() < now => {
+                self.entries.remove(token);
+                false
+            }
+            Some(_) => same_secret,
+        };
+
+        if !hit {
+            let rwt = Rwt::<T>::parse_verified(token, secret)?;
+            rwt.check_expiry(now)?;
+            if !same_secret {
+                self.entries.clear();
+                self.secret = Some(SecretKey::new(secret));
+            }
+            self.make_room();
+            self.entries.insert(token.to_owned(), rwt.payload);
+        }
+
+        Ok(&self.entries[token])
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::TokenCache;
+    use crate::{Clock, Error, Expiring, Rwt};
+    use serde::{Deserialize, Serialize};
+    use std::cell::Cell;
+    use std::str::FromStr;
+
+    #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        jti: String,
+        exp: i64,
+    }
+
+    impl Expiring for Payload {
+        fn expiry(&self) -> i64 {
+            self.exp
+        }
+    }
+
+    impl FromStr for Payload {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    /// A clock the test can move along.
+    struct TestClock(Cell<i64>);
+
+    impl Clock for TestClock {
+        fn now(&self) -> i64 {
+            self.0.get()
+        }
+    }
+
+    fn payload(exp: i64) -> Payload {
+        Payload {
+            jti: "this one".into(),
+            exp,
+        }
+    }
+
+    fn token(exp: i64) -> String {
+        Rwt::with_payload(payload(exp), "secret")
+            .unwrap()
+            .encode()
+            .unwrap()
+    }
+
+    #[test]
+    fn cache_hit() {
+        let clock = TestClock(Cell::new(10));
+        let mut cache = TokenCache::<Payload, _>::new(&clock);
+        let token = token(13);
+
+        assert_eq!(13, cache.get_verified(&token, "secret").unwrap().exp);
+        assert_eq!(1, cache.len());
+        assert_eq!(13, cache.get_verified(&token, "secret").unwrap().exp);
+        assert_eq!(1, cache.len());
+
+        // A cached token is no good to someone holding the wrong secret.
+        match cache.get_verified(&token, "other secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn expired_entries_are_evicted() {
+        let clock = TestClock(Cell::new(10));
+        let mut cache = TokenCache::<Payload, _>::new(&clock);
+        let (short, long) = (token(13), token(20));
+        cache.get_verified(&short, "secret").unwrap();
+        cache.get_verified(&long, "secret").unwrap();
+        assert_eq!(2, cache.len());
+
+        clock.0.set(14);
+        match cache.get_verified(&short, "secret") {
+            Err(Error::Expired) => (),
+            other => panic!("expected Expired, got {:?}", other),
+        }
+        assert_eq!(1, cache.len());
+
+        // Evicted, the token has to be verified again, and it's no fresher than it was.
+        match cache.get_verified(&short, "secret") {
+            Err(Error::Expired) => (),
+            other => panic!("expected Expired, got {:?}", other),
+        }
+        assert_eq!(1, cache.len());
+
+        clock.0.set(21);
+        cache.evict_expired();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn full_cache_evicts_soonest_to_expire() {
+        let clock = TestClock(Cell::new(10));
+        let mut cache = TokenCache::<Payload, _>::with_capacity(&clock, 2);
+        let (a, b, c) = (token(30), token(20), token(40));
+        cache.get_verified(&a, "secret").unwrap();
+        cache.get_verified(&b, "secret").unwrap();
+        cache.get_verified(&c, "secret").unwrap();
+        assert_eq!(2, cache.len());
+        assert!(cache.entries.contains_key(&a));
+        assert!(!cache.entries.contains_key(&b));
+        assert!(cache.entries.contains_key(&c));
+
+        // Expired entries go first, however far off the others are.
+        clock.0.set(35);
+        let d = token(100);
+        cache.get_verified(&d, "secret").unwrap();
+        assert_eq!(2, cache.len());
+        assert!(!cache.entries.contains_key(&a));
+        assert!(cache.entries.contains_key(&c));
+
+        let mut cache = TokenCache::<Payload, _>::with_capacity(&clock, 0);
+        assert_eq!(100, cache.get_verified(&d, "secret").unwrap().exp);
+        assert_eq!(1, cache.len());
+    }
+
+    #[test]
+    fn new_secret_starts_the_cache_over() {
+        let clock = TestClock(Cell::new(10));
+        let mut cache = TokenCache::<Payload, _>::new(&clock);
+        cache.get_verified(&token(13), "secret").unwrap();
+        cache.get_verified(&token(14), "secret").unwrap();
+        assert_eq!(2, cache.len());
+
+        let other = Rwt::with_payload(payload(13), "other secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+        cache.get_verified(&other, "other secret").unwrap();
+        assert_eq!(1, cache.len());
+
+        // The old secret's tokens are verified afresh, not served.
+        match cache.get_verified(&other, "secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+        assert_eq!(1, cache.len());
+    }
+
+    #[test]
+    fn tampered_token_is_never_cached() {
+        let clock = TestClock(Cell::new(10));
+        let mut cache = TokenCache::<Payload, _>::new(&clock);
+        let (_, signature) = token(13)
+            .split_once('.')
+            .map(|(b, s)| (b, s.to_owned()))
+            .unwrap();
+        let body = base64::encode(r#"{"jti":"that one","exp":99}"#);
+        let forged = format!("{}.{}", body, signature);
+
+        for _ in 0..2 {
+            match cache.get_verified(&forged, "secret") {
+                Err(Error::InvalidSignature) => (),
+                other => panic!("expected InvalidSignature, got {:?}", other),
+            }
+        }
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{Clock, Error, Expiring, ExpiringMut, IssuedAt, IssuedAtMut, NotBefore, Result, Rwt};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The registered claims everyone ends up reimplementing.
+///
+/// Every field is optional, so you can set only the ones you care about:
+///
+/// ```
+/// # use rwt::{Claims, Rwt};
+/// let claims = Claims {
+///     sub: Some("archer884".to_owned()),
+///     exp: Some(1_600_000_000),
+///     ..Default::default()
+/// };
+///
+/// let token = Rwt::with_payload(claims, "secret").unwrap();
+/// assert!(!token.payload.expired(1_500_000_000));
+/// ```
+///
+/// Missing claims are left out of the serialized form entirely.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Claims {
+    /// Subject: whom the token refers to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    /// Issuer: who created and signed the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    /// Audience: whom the token is intended for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Audience>,
+    /// Expiration time, in Unix seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    /// Not-before time, in Unix seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    /// Issued-at time, in Unix seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    /// Token id: a unique identifier for the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+impl Claims {
+    /// Check whether the claims have expired according to `clock`.
+    ///
+    /// Claims without an `exp` never expire.
+    #[must_use = "verification result must be checked"]
+    pub fn expired(&self, clock: impl Clock) -> bool {
+        self.exp.is_some_and(|exp| clock.now() > exp)
+    }
+
+    /// Check whether the claims were issued by `expected`.
+    ///
+    /// Claims without an `iss` weren't issued by anyone in particular, so this returns `false`.
+    #[must_use = "verification result must be checked"]
+    pub fn has_issuer(&self, expected: &str) -> bool {
+        self.iss.as_deref() == Some(expected)
+    }
+
+    /// Check whether the claims were intended for `expected`.
+    ///
+    /// This works whether the audience is a single string or a list. Claims without an `aud` (or
+    /// with an empty list) aren't intended for anyone in particular, so this returns `false`.
+    #[must_use = "verification result must be checked"]
+    pub fn has_audience(&self, expected: &str) -> bool {
+        self.aud.as_ref().is_some_and(|aud| aud.contains(expected))
+    }
+}
+
+/// The `aud` claim, which JWT allows to be either a single string or a list of them.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    /// Check whether `expected` is among the audience.
+    pub fn contains(&self, expected: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == expected,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == expected),
+        }
+    }
+}
+
+impl From<String> for Audience {
+    fn from(aud: String) -> Self {
+        Audience::One(aud)
+    }
+}
+
+impl From<&str> for Audience {
+    fn from(aud: &str) -> Self {
+        Audience::One(aud.into())
+    }
+}
+
+impl From<Vec<String>> for Audience {
+    fn from(auds: Vec<String>) -> Self {
+        Audience::Many(auds)
+    }
+}
+
+impl<T: Serialize, A> Rwt<T, A> {
+    /// Read the registered claims out of the payload, as JWT-native middleware would.
+    ///
+    /// This works for any payload that serializes to a JSON object, whatever it calls its
+    /// fields internally, so long as the claims go by their JWT names: `sub`, `iss`, `aud`,
+    /// `exp`, `nbf`, `iat`, and `jti`. Every claim is optional, and one that's missing or `null`
+    /// comes back as `None`; anything else in the payload is ignored. `aud` may be a string or a
+    /// list of them. The dates are NumericDates, which JWT allows to have a fraction; that's
+    /// rounded down to the second. A claim of the wrong type, like a date written as a string, is
+    /// rejected with `Error::Format`.
+    ///
+    /// As with `payload`, **this says nothing about the signature**.
+    pub fn as_jwt_claims(&self) -> Result<Claims> {
+        let payload = serde_json::to_value(&self.payload)?;
+        let claims = payload
+            .as_object()
+            .ok_or_else(|| Error::Format("Payload is not a JSON object".into()))?;
+
+        Ok(Claims {
+            sub: string_claim(claims, "sub")?,
+            iss: string_claim(claims, "iss")?,
+            aud: match claim(claims, "aud") {
+                None => None,
+                Some(aud) => Some(
+                    Audience::deserialize(aud)
+                        .map_err(|_| Error::Format("aud is not a string or a list".into()))?,
+                ),
+            },
+            exp: numeric_date(claims, "exp")?,
+            nbf: numeric_date(claims, "nbf")?,
+            iat: numeric_date(claims, "iat")?,
+            jti: string_claim(claims, "jti")?,
+        })
+    }
+}
+
+/// A claim, unless it's missing or `null`.
+fn claim<'a>(claims: &'a Map<String, Value>, name: &str) -> Option<&'a Value> {
+    claims.get(name).filter(|value| !value.is_null())
+}
+
+fn string_claim(claims: &Map<String, Value>, name: &str) -> Result<Option<String>> {
+    match claim(claims, name) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(Error::Format(format!("{} is not a string", name))),
+    }
+}
+
+/// A JWT NumericDate, in whole seconds.
+fn numeric_date(claims: &Map<String, Value>, name: &str) -> Result<Option<i64>> {
+    let value = match claim(claims, name) {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+    let seconds = value.as_i64().or_else(|| {
+        value
+            .as_f64()
+            .map(f64::floor)
+            .filter(|&seconds| seconds >= i64::MIN as f64 && seconds < i64::MAX as f64)
+            .map(|seconds| seconds as i64)
+    });
+    match seconds {
+        Some(seconds) => Ok(Some(seconds)),
+        None => Err(Error::Format(format!("{} is not a NumericDate", name))),
+    }
+}
+
+impl FromStr for Claims {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+/// Claims without an `exp` never expire.
+impl Expiring for Claims {
+    fn expiry(&self) -> i64 {
+        self.exp.unwrap_or(i64::MAX)
+    }
+}
+
+/// Claims without an `nbf` are valid from the beginning of time.
+impl NotBefore for Claims {
+    fn not_before(&self) -> i64 {
+        self.nbf.unwrap_or(i64::MIN)
+    }
+}
+
+/// Claims without an `iat` are treated as infinitely old.
+impl IssuedAt for Claims {
+    fn issued_at(&self) -> i64 {
+        self.iat.unwrap_or(i64::MIN)
+    }
+}
+
+impl ExpiringMut for Claims {
+    fn set_expiry(&mut self, exp: i64) {
+        self.exp = Some(exp);
+    }
+}
+
+impl IssuedAtMut for Claims {
+    fn set_issued_at(&mut self, iat: i64) {
+        self.iat = Some(iat);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Audience, Claims};
+    use crate::{Error, Rwt};
+    use serde::Serialize;
+    use serde_json::json;
+
+    fn claims() -> Claims {
+        Claims {
+            sub: Some("archer884".to_owned()),
+            iss: Some("rwt".to_owned()),
+            aud: Some("my-api".into()),
+            exp: Some(200),
+            nbf: Some(100),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn claims_round_trip() {
+        let rwt = Rwt::with_payload(claims(), "secret").unwrap();
+        let encoded = rwt.encode().unwrap();
+        let parsed = Rwt::<Claims>::parse_verified(&encoded, "secret").unwrap();
+        assert_eq!(claims(), parsed.payload);
+    }
+
+    #[test]
+    fn missing_claims_are_not_serialized() {
+        let json = serde_json::to_string(&Claims {
+            sub: Some("archer884".to_owned()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(r#"{"sub":"archer884"}"#, json);
+    }
+
+    #[test]
+    fn claims_validation() {
+        let claims = claims();
+        assert!(!claims.expired(200));
+        assert!(claims.expired(201));
+        assert!(claims.has_issuer("rwt"));
+        assert!(!claims.has_issuer("someone else"));
+        assert!(claims.has_audience("my-api"));
+        assert!(!claims.has_audience("their-api"));
+
+        let rwt = Rwt::with_payload(claims, "secret").unwrap();
+        assert!(rwt.is_active(100, 0));
+        assert!(!rwt.is_expired(200));
+    }
+
+    #[test]
+    fn empty_claims_are_permissive_about_time() {
+        let rwt = Rwt::with_payload(Claims::default(), "secret").unwrap();
+        assert!(!rwt.payload.expired(i64::MAX));
+        assert!(!rwt.is_expired(i64::MAX));
+        assert!(rwt.is_active(i64::MIN, 0));
+        assert!(!rwt.payload.has_issuer(""));
+        assert!(rwt.older_than(i64::MAX, i64::MAX - 1));
+    }
+
+    #[test]
+    fn stamp_claims_issued_at() {
+        let rwt = Rwt::builder()
+            .payload(Claims::default())
+            .issued_at(100)
+            .sign("secret")
+            .unwrap();
+        assert_eq!(Some(100), rwt.payload.iat);
+    }
+
+    #[test]
+    fn audience_as_string() {
+        let claims: Claims = r#"{"aud":"my-api"}"#.parse().unwrap();
+        assert_eq!(Some(Audience::One("my-api".to_owned())), claims.aud);
+        assert!(claims.has_audience("my-api"));
+        assert_eq!(
+            r#"{"aud":"my-api"}"#,
+            serde_json::to_string(&claims).unwrap()
+        );
+    }
+
+    #[test]
+    fn audience_as_list() {
+        let claims: Claims = r#"{"aud":["other","my-api"]}"#.parse().unwrap();
+        assert!(claims.has_audience("my-api"));
+        assert!(claims.has_audience("other"));
+        assert!(!claims.has_audience("their-api"));
+        assert_eq!(
+            r#"{"aud":["other","my-api"]}"#,
+            serde_json::to_string(&claims).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_or_empty_audience() {
+        assert!(!Claims::default().has_audience("my-api"));
+
+        let claims: Claims = r#"{"aud":[]}"#.parse().unwrap();
+        assert_eq!(Some(Audience::Many(Vec::new())), claims.aud);
+        assert!(!claims.has_audience("my-api"));
+    }
+
+    #[test]
+    fn jwt_claims_round_trip() {
+        let many = Claims {
+            aud: Some(Audience::Many(vec![
+                "other".to_owned(),
+                "my-api".to_owned(),
+            ])),
+            iat: Some(50),
+            jti: Some("this one".to_owned()),
+            ..claims()
+        };
+        for claims in &[claims(), many] {
+            let jwt = Rwt::with_payload(claims.clone(), "secret")
+                .unwrap()
+                .encode_jwt("secret")
+                .unwrap();
+            let parsed = Rwt::<serde_json::Value>::parse_jwt(&jwt, "secret").unwrap();
+            assert_eq!(*claims, parsed.as_jwt_claims().unwrap());
+        }
+    }
+
+    #[test]
+    fn jwt_claims_from_custom_payload() {
+        #[derive(Serialize)]
+        struct Session {
+            sub: &'static str,
+            aud: Vec<&'static str>,
+            exp: f64,
+            iss: Option<String>,
+            scope: &'static str,
+        }
+
+        let rwt = Rwt::with_payload(
+            Session {
+                sub: "archer884",
+                aud: vec!["my-api"],
+                exp: 200.75,
+                iss: None,
+                scope: "admin",
+            },
+            "secret",
+        )
+        .unwrap();
+        let claims = rwt.as_jwt_claims().unwrap();
+        assert_eq!(Some("archer884".to_owned()), claims.sub);
+        assert_eq!(Some(Audience::Many(vec!["my-api".to_owned()])), claims.aud);
+        assert_eq!(Some(200), claims.exp);
+        assert_eq!(None, claims.iss);
+        assert_eq!(None, claims.nbf);
+    }
+
+    #[test]
+    fn jwt_claims_of_the_wrong_type() {
+        let payloads = [
+            json!("not an object"),
+            json!({ "sub": 884 }),
+            json!({ "aud": [1, 2] }),
+            json!({ "exp": "200" }),
+            json!({ "nbf": 1e300 }),
+        ];
+        for payload in &payloads {
+            let rwt = Rwt::with_payload(payload, "secret").unwrap();
+            match rwt.as_jwt_claims() {
+                Err(Error::Format(_)) => (),
+                other => panic!("expected Format error, got {:?}", other),
+            }
+        }
+    }
+}
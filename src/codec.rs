@@ -0,0 +1,152 @@
+use crate::Result;
+use alloc::string::String;
+use alloc::vec::Vec;
+use base64::DecodeError;
+
+/// A base64 encoding.
+///
+/// Tokens use standard, padded base64 unless told otherwise, and a `base64::Config` is a codec
+/// in its own right, so `base64::URL_SAFE_NO_PAD` and friends can be passed straight to
+/// `Rwt::encode_with_codec` and `Rwt::parse_with_codec`. Implement this yourself for anything
+/// else: a different alphabet, or a faster engine.
+///
+/// A `base64::Config` scans its input for characters outside its alphabet before decoding, so
+/// garbage is turned away before anything is allocated for it.
+pub trait Base64Codec {
+    fn encode(&self, bytes: &[u8]) -> String;
+    fn decode(&self, s: &str) -> Result<Vec<u8>>;
+}
+
+impl Base64Codec for base64::Config {
+    fn encode(&self, bytes: &[u8]) -> String {
+        base64::encode_config(bytes, *self)
+    }
+
+    fn decode(&self, s: &str) -> Result<Vec<u8>> {
+        check_alphabet(s, *self)?;
+        Ok(base64::decode_config(s, *self)?)
+    }
+}
+
+/// Reject the first character that isn't in `config`'s alphabet, as the decoder itself would.
+///
+/// The config won't say which alphabet it uses, but every one of them is the letters and digits
+/// plus two more, and encoding the right three bytes (symbols 62, 63, 0, and 1) turns those two
+/// up wherever they live. Padding is left to the decoder.
+fn check_alphabet(s: &str, config: base64::Config) -> Result<()> {
+    let mut symbols = [0; 4];
+    base64::encode_config_slice([0xfb, 0xf0, 0x01], config, &mut symbols);
+
+    let mut valid = [false; 256];
+    for b in (b'0'..=b'9').chain(b'A'..=b'Z').chain(b'a'..=b'z') {
+        valid[b as usize] = true;
+    }
+    for &b in symbols.iter().chain(b"=") {
+        valid[b as usize] = true;
+    }
+
+    let invalid = s.bytes().position(|b| !valid[b as usize]);
+    match invalid {
+        None => Ok(()),
+        Some(index) => Err(DecodeError::InvalidByte(index, s.as_bytes()[index]).into()),
+    }
+}
+
+impl<C: Base64Codec + ?Sized> Base64Codec for &C {
+    fn encode(&self, bytes: &[u8]) -> String {
+        (**self).encode(bytes)
+    }
+
+    fn decode(&self, s: &str) -> Result<Vec<u8>> {
+        (**self).decode(s)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::Base64Codec;
+    use crate::{Error, Result, Rwt};
+    use base64::DecodeError;
+    use serde_json::{json, Value};
+    use std::cell::Cell;
+
+    /// Standard base64, with a tally.
+    #[derive(Default)]
+    struct Counting {
+        encoded: Cell<usize>,
+        decoded: Cell<usize>,
+    }
+
+    impl Base64Codec for Counting {
+        fn encode(&self, bytes: &[u8]) -> String {
+            self.encoded.set(self.encoded.get() + 1);
+            base64::STANDARD.encode(bytes)
+        }
+
+        fn decode(&self, s: &str) -> Result<Vec<u8>> {
+            self.decoded.set(self.decoded.get() + 1);
+            base64::STANDARD.decode(s)
+        }
+    }
+
+    #[test]
+    fn custom_codec_is_used() {
+        let codec = Counting::default();
+        let rwt = Rwt::with_payload_kid(json!({ "sub": "alice" }), "secret", "k1").unwrap();
+
+        let encoded = rwt.encode_with_codec(&codec).unwrap();
+        assert_eq!(rwt.encode().unwrap(), encoded);
+        assert_eq!(3, codec.encoded.get());
+
+        let parsed = Rwt::<Value>::parse_with_codec(&encoded, &codec).unwrap();
+        assert_eq!(3, codec.decoded.get());
+        assert_eq!(rwt, parsed);
+    }
+
+    #[test]
+    fn out_of_alphabet_characters_are_rejected_up_front() {
+        let cases = [
+            (base64::STANDARD, "c2VjcmV0-", 8, b'-'),
+            (base64::STANDARD, "!c2VjcmV0", 0, b'!'),
+            (base64::URL_SAFE_NO_PAD, "c2Vj+cmV0", 4, b'+'),
+            (base64::URL_SAFE_NO_PAD, "c2VjcmV0/", 8, b'/'),
+            (base64::CRYPT, "c2Vj_", 4, b'_'),
+        ];
+        for &(config, s, index, byte) in &cases {
+            match config.decode(s) {
+                Err(Error::Base64(DecodeError::InvalidByte(i, b))) => {
+                    assert_eq!((index, byte), (i, b), "{:?}", s)
+                }
+                other => panic!("expected InvalidByte, got {:?}", other),
+            }
+        }
+
+        // Each alphabet's own extras get through to the decoder.
+        assert_eq!(b"\xfb\xf0", &*base64::STANDARD.decode("+/A=").unwrap());
+        assert_eq!(
+            b"\xfb\xf0",
+            &*base64::URL_SAFE_NO_PAD.decode("-_A").unwrap()
+        );
+
+        let token = Rwt::with_payload(json!({ "sub": "alice" }), "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+        let garbled = token.replacen('e', "\u{e9}", 1);
+        match garbled.parse::<Rwt<Value>>() {
+            Err(Error::Base64(DecodeError::InvalidByte(_, 0xc3))) => (),
+            other => panic!("expected InvalidByte, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn config_is_a_codec() {
+        let rwt = Rwt::with_payload(json!({ "sub": "alice" }), "secret").unwrap();
+        let encoded = rwt.encode_with_codec(base64::URL_SAFE_NO_PAD).unwrap();
+        assert_eq!(rwt.encode_url_safe().unwrap(), encoded);
+        assert_eq!(
+            rwt,
+            Rwt::parse_with_codec(&encoded, base64::URL_SAFE_NO_PAD).unwrap()
+        );
+    }
+}
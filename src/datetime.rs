@@ -0,0 +1,53 @@
+use crate::Clock;
+use chrono::{DateTime, TimeZone};
+
+/// A `DateTime` is a clock stuck at that moment, so `token.is_expired(Utc::now())` does what it
+/// says.
+///
+/// For a payload holding `DateTime`s of its own, `#[serde(with = "chrono::serde::ts_seconds")]`
+/// keeps them in Unix seconds on the wire, and `Expiring` and friends can return
+/// `self.exp.timestamp()`.
+impl<Tz: TimeZone> Clock for DateTime<Tz> {
+    fn now(&self) -> i64 {
+        self.timestamp()
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{Expiring, Rwt};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        exp: DateTime<Utc>,
+    }
+
+    impl Expiring for Payload {
+        fn expiry(&self) -> i64 {
+            self.exp.timestamp()
+        }
+    }
+
+    fn exp() -> DateTime<Utc> {
+        Utc.timestamp_opt(1_600_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn datetime_serializes_as_unix_seconds() {
+        let payload = Payload { exp: exp() };
+        let json = serde_json::to_string(&payload).unwrap();
+        assert_eq!(r#"{"exp":1600000000}"#, json);
+        assert_eq!(payload, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn is_expired_against_datetime() {
+        let rwt = Rwt::with_payload(Payload { exp: exp() }, "secret").unwrap();
+        assert!(!rwt.is_expired(exp()));
+        assert!(rwt.is_expired(exp() + Duration::seconds(1)));
+        assert!(rwt.is_expired(Utc::now()));
+    }
+}
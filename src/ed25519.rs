@@ -0,0 +1,132 @@
+use crate::{Algorithm, Format, Header, Result, Rwt, Signable};
+use core::marker::PhantomData;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+impl<T: Signable> Rwt<T> {
+    /// Create a web token with the provided payload, signed with an Ed25519 private key.
+    ///
+    /// Unlike an HMAC, which anyone who can verify could also forge, an Ed25519 signature can be
+    /// checked by anyone holding the public key but created only by the holder of the private key.
+    /// The 64-byte signature goes in the usual signature slot, so the encoded form looks the same.
+    pub fn sign_ed25519(payload: T, key: &SigningKey) -> Result<Rwt<T>> {
+        let header = Header::new(Algorithm::Ed25519, None);
+        let input = Format::Json.serialize(&payload)?;
+        let signature = key.sign(&header.signing_input(&input)?);
+        Ok(Rwt {
+            payload,
+            signature: base64::encode(signature.to_bytes()),
+            header,
+            format: Format::Json,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<T: Signable, A> Rwt<T, A> {
+    /// Verify a token created by `sign_ed25519` against the signer's public key.
+    ///
+    /// This uses `verify_strict`, which rejects the weak and malleable signatures that plain
+    /// Ed25519 verification lets through. As with `verify_with_alg`, a token whose header names
+    /// any other algorithm is rejected out of hand, so parse tokens to be checked this way as
+    /// `Rwt<T, Ed25519>`.
+    #[must_use = "verification result must be checked"]
+    pub fn verify_ed25519(&self, key: &VerifyingKey) -> bool {
+        if self.header.alg != Algorithm::Ed25519 {
+            return false;
+        }
+        let input = match self.signing_input() {
+            Err(_) => return false,
+            Ok(input) => input,
+        };
+
+        let signature = base64::decode(&self.signature)
+            .ok()
+            .and_then(|signature| Signature::from_slice(&signature).ok());
+
+        match signature {
+            None => false,
+            Some(signature) => key.verify_strict(&input, &signature).is_ok(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{Algorithm, Ed25519, Rwt};
+    use ed25519_dalek::SigningKey;
+    use serde::{Deserialize, Serialize};
+    use std::str::FromStr;
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        jti: String,
+    }
+
+    impl FromStr for Payload {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    fn payload() -> Payload {
+        Payload {
+            jti: "this one".to_owned(),
+        }
+    }
+
+    #[test]
+    fn ed25519_round_trip() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let rwt = Rwt::sign_ed25519(payload(), &key).unwrap();
+        assert_eq!(64, rwt.signature_bytes().unwrap().len());
+
+        let parsed = rwt
+            .encode()
+            .unwrap()
+            .parse::<Rwt<Payload, Ed25519>>()
+            .unwrap();
+        assert!(parsed.verify_ed25519(&key.verifying_key()));
+    }
+
+    #[test]
+    fn ed25519_rejects_other_algorithms() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut rwt = Rwt::sign_ed25519(payload(), &key).unwrap();
+        rwt.header.alg = Algorithm::HmacSha256;
+        assert!(!rwt.verify_ed25519(&key.verifying_key()));
+
+        // Parsed as a plain `Rwt<T>`, the token is taken for HMAC-SHA256.
+        let encoded = Rwt::sign_ed25519(payload(), &key)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let parsed = encoded.parse::<Rwt<Payload>>().unwrap();
+        assert!(!parsed.verify_ed25519(&key.verifying_key()));
+    }
+
+    #[test]
+    fn ed25519_rejects_wrong_key() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let rwt = Rwt::sign_ed25519(payload(), &key).unwrap();
+        assert!(!rwt.verify_ed25519(&other.verifying_key()));
+    }
+
+    #[test]
+    fn ed25519_rejects_tampered_payload() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut rwt = Rwt::sign_ed25519(payload(), &key).unwrap();
+        rwt.payload.jti = "that one".to_owned();
+        assert!(!rwt.verify_ed25519(&key.verifying_key()));
+    }
+
+    #[test]
+    fn ed25519_is_not_a_shared_secret_algorithm() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let rwt = Rwt::sign_ed25519(payload(), &key).unwrap();
+        assert!(!rwt.is_valid(key.to_bytes()));
+        assert!(Rwt::with_payload_alg(payload(), "secret", Algorithm::Ed25519).is_err());
+    }
+}
@@ -1,37 +1,141 @@
+use alloc::format;
+use alloc::string::String;
 use base64::DecodeError as Base64Error;
+use core::fmt;
+use core::str::Utf8Error;
+#[cfg(feature = "serde")]
 use serde_json::Error as JsonError;
-use std::{error, fmt};
-use std::str::Utf8Error;
+#[cfg(feature = "std")]
+use std::error;
 
-#[derive(Debug)]
+/// Everything that can go wrong with a token.
+///
+/// More variants will turn up as validation grows, so match with a wildcard arm.
+///
+/// Messages quote the input that caused them, which is handy in development and less so in a log
+/// file. Build with the `terse-errors` feature to leave the input out. That goes for JSON and
+/// MessagePack errors, too, which would otherwise quote pieces of the payload: they say only that
+/// the payload couldn't be read or written, in `Display` and `Debug` alike. The wrapped error is
+/// still there for `source` if you want the details.
+#[cfg_attr(not(feature = "terse-errors"), derive(Debug))]
+#[non_exhaustive]
 pub enum Error {
     Base64(Base64Error),
+    ClaimRejected(String),
     Encoding(Utf8Error),
+    Expired,
     Format(String),
     FromStr(String),
+    InvalidSignature,
+    #[cfg(feature = "serde")]
     Json(JsonError),
+    #[cfg(feature = "msgpack")]
+    MessagePackDecode(rmp_serde::decode::Error),
+    #[cfg(feature = "msgpack")]
+    MessagePackEncode(rmp_serde::encode::Error),
+    NotYetValid,
+}
+
+/// An error message, along with the input that caused it.
+///
+/// Built with the `terse-errors` feature, the input is left off. What's left says what went wrong
+/// but nothing about the token it went wrong with, so errors can be logged without leaking
+/// pieces of tokens (or payloads) into the logs.
+pub(crate) fn with_input(message: &str, input: impl fmt::Debug) -> String {
+    if cfg!(feature = "terse-errors") {
+        message.into()
+    } else {
+        format!("{}: {:?}", message, input)
+    }
+}
+
+impl Error {
+    /// A short, fixed name for what went wrong, for logs.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn category(&self) -> &'static str {
+        match *self {
+            Error::Base64(_) => "base64",
+            Error::ClaimRejected(_) => "claim",
+            Error::Encoding(_) => "utf8",
+            Error::Expired => "expired",
+            Error::Format(_) => "format",
+            Error::FromStr(_) => "payload",
+            Error::InvalidSignature => "signature",
+            #[cfg(feature = "serde")]
+            Error::Json(_) => "json",
+            #[cfg(feature = "msgpack")]
+            Error::MessagePackDecode(_) | Error::MessagePackEncode(_) => "msgpack",
+            Error::NotYetValid => "not_yet_valid",
+        }
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::Base64(ref e) => write!(f, "Error in base64 encoding: {}", e),
+            Error::ClaimRejected(ref e) => write!(f, "Claim rejected: {}", e),
             Error::Encoding(ref e) => write!(f, "Error in utf8 encoding: {}", e),
+            Error::Expired => write!(f, "Token has expired"),
             Error::Format(ref e) => write!(f, "Error in token format: {}", e),
             Error::FromStr(ref e) => write!(f, "Error in parsing value: {}", e),
+            Error::InvalidSignature => write!(f, "Invalid token signature"),
+            #[cfg(all(feature = "serde", not(feature = "terse-errors")))]
             Error::Json(ref e) => write!(f, "Error in json serialization: {}", e),
+            #[cfg(all(feature = "serde", feature = "terse-errors"))]
+            Error::Json(_) => write!(f, "Error in json serialization"),
+            #[cfg(all(feature = "msgpack", not(feature = "terse-errors")))]
+            Error::MessagePackDecode(ref e) => write!(f, "Error in msgpack deserialization: {}", e),
+            #[cfg(all(feature = "msgpack", feature = "terse-errors"))]
+            Error::MessagePackDecode(_) => write!(f, "Error in msgpack deserialization"),
+            #[cfg(feature = "msgpack")]
+            Error::MessagePackEncode(ref e) => write!(f, "Error in msgpack serialization: {}", e),
+            Error::NotYetValid => write!(f, "Token is not yet valid"),
+        }
+    }
+}
+
+/// The derived `Debug`, except that errors from the payload's format are left opaque.
+#[cfg(feature = "terse-errors")]
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Base64(ref e) => f.debug_tuple("Base64").field(e).finish(),
+            Error::ClaimRejected(ref e) => f.debug_tuple("ClaimRejected").field(e).finish(),
+            Error::Encoding(ref e) => f.debug_tuple("Encoding").field(e).finish(),
+            Error::Expired => f.write_str("Expired"),
+            Error::Format(ref e) => f.debug_tuple("Format").field(e).finish(),
+            Error::FromStr(ref e) => f.debug_tuple("FromStr").field(e).finish(),
+            Error::InvalidSignature => f.write_str("InvalidSignature"),
+            #[cfg(feature = "serde")]
+            Error::Json(_) => f.write_str("Json(..)"),
+            #[cfg(feature = "msgpack")]
+            Error::MessagePackDecode(_) => f.write_str("MessagePackDecode(..)"),
+            #[cfg(feature = "msgpack")]
+            Error::MessagePackEncode(ref e) => f.debug_tuple("MessagePackEncode").field(e).finish(),
+            Error::NotYetValid => f.write_str("NotYetValid"),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for Error {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
-            Error::Base64(_) => "Error in base64 encoding",
-            Error::Encoding(_) => "Error in utf8 encoding",
-            Error::Format(_) => "Error in token format",
-            Error::FromStr(_) => "Error in parsing value",
-            Error::Json(_) => "Error in json serialization",
+            Error::Base64(ref e) => Some(e),
+            Error::Encoding(ref e) => Some(e),
+            #[cfg(feature = "serde")]
+            Error::Json(ref e) => Some(e),
+            #[cfg(feature = "msgpack")]
+            Error::MessagePackDecode(ref e) => Some(e),
+            #[cfg(feature = "msgpack")]
+            Error::MessagePackEncode(ref e) => Some(e),
+            Error::ClaimRejected(_)
+            | Error::Expired
+            | Error::Format(_)
+            | Error::FromStr(_)
+            | Error::InvalidSignature
+            | Error::NotYetValid => None,
         }
     }
 }
@@ -48,8 +152,148 @@ impl From<Utf8Error> for Error {
     }
 }
 
+#[cfg(feature = "serde")]
 impl From<JsonError> for Error {
     fn from(error: JsonError) -> Self {
         Error::Json(error)
     }
 }
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(error: rmp_serde::decode::Error) -> Self {
+        Error::MessagePackDecode(error)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(error: rmp_serde::encode::Error) -> Self {
+        Error::MessagePackEncode(error)
+    }
+}
+
+#[cfg(all(test, feature = "serde", feature = "terse-errors"))]
+mod terse_tests {
+    use crate::{Error, Rwt};
+    use std::str::FromStr;
+
+    const SECRET_STUFF: &str = "c2VjcmV0IHN0dWZm";
+
+    /// A payload whose parse errors give away everything.
+    #[derive(Debug, serde::Serialize)]
+    struct Leaky;
+
+    impl FromStr for Leaky {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            Err(s.into())
+        }
+    }
+
+    fn assert_terse(error: Error) {
+        for message in &[error.to_string(), format!("{:?}", error)] {
+            assert!(!message.contains(SECRET_STUFF), "{} leaks input", message);
+            assert!(
+                !message.contains("secret stuff"),
+                "{} leaks payload",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn terse_errors_leave_out_the_token() {
+        let malformed = [
+            SECRET_STUFF.to_owned(),
+            format!("{}.", SECRET_STUFF),
+            format!("{0}.{0}.{0}.{0}", SECRET_STUFF),
+        ];
+        for token in &malformed {
+            assert_terse(token.parse::<Rwt<Leaky>>().unwrap_err());
+            assert_terse(Rwt::<Leaky>::parse_verified(token, "secret").unwrap_err());
+        }
+
+        let encoded = Rwt::with_payload("secret stuff", "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+        match encoded.parse::<Rwt<Leaky>>() {
+            Err(error @ Error::FromStr(_)) => assert_terse(error),
+            other => panic!("expected FromStr error, got {:?}", other),
+        }
+
+        assert_terse(Rwt::<Leaky>::from_bearer(&format!("{} token", SECRET_STUFF)).unwrap_err());
+        assert_terse(Rwt::<Leaky>::parse_jwt(SECRET_STUFF, "secret").unwrap_err());
+    }
+
+    #[test]
+    fn terse_errors_leave_out_mistyped_payloads() {
+        #[derive(Debug, serde::Serialize, serde::Deserialize)]
+        struct Payload {
+            exp: i64,
+        }
+
+        impl FromStr for Payload {
+            type Err = serde_json::Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                serde_json::from_str(s)
+            }
+        }
+
+        #[derive(serde::Serialize)]
+        struct Mistyped {
+            exp: &'static str,
+        }
+
+        let encoded = Rwt::with_payload(
+            Mistyped {
+                exp: "secret stuff",
+            },
+            "secret",
+        )
+        .unwrap()
+        .encode()
+        .unwrap();
+        match Rwt::<Payload>::from_json_str(&encoded) {
+            Err(error @ Error::Json(_)) => assert_terse(error),
+            other => panic!("expected Json error, got {:?}", other),
+        }
+        match encoded.parse::<Rwt<Payload>>() {
+            Err(error @ Error::FromStr(_)) => assert_terse(error),
+            other => panic!("expected FromStr error, got {:?}", other),
+        }
+
+        let header = base64::encode(r#"{"alg":"secret stuff","typ":"RWT"}"#);
+        let body = encoded.split('.').next().unwrap();
+        let token = format!("${}.{}.sig", header, body);
+        match token.parse::<Rwt<Payload>>() {
+            Err(error @ Error::Json(_)) => assert_terse(error),
+            other => panic!("expected Json error, got {:?}", other),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Base64Error, Error};
+    use std::error::Error as _;
+
+    #[test]
+    fn source_is_wrapped_error() {
+        let error = Error::from(base64::decode("!").unwrap_err());
+        let source = error.source().unwrap();
+        assert!(source.downcast_ref::<Base64Error>().is_some());
+    }
+
+    #[test]
+    fn string_variants_have_no_source() {
+        assert!(Error::Format("Missing body".to_owned()).source().is_none());
+        assert!(Error::InvalidSignature.source().is_none());
+        assert!(Error::ClaimRejected("tenant".to_owned()).source().is_none());
+        assert!(Error::Expired.source().is_none());
+        assert!(Error::NotYetValid.source().is_none());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{strip_bearer, Rwt};
+use axum_core::extract::FromRequestParts;
+use core::fmt::Debug;
+use core::str::FromStr;
+use http::header::AUTHORIZATION;
+use http::request::Parts;
+use http::StatusCode;
+
+/// Application state that knows the secret tokens are signed with.
+///
+/// Implement this for your router's state to extract a verified `Rwt<T>` straight into a
+/// handler's arguments.
+pub trait RwtSecret {
+    fn rwt_secret(&self) -> &[u8];
+}
+
+/// Extract a verified token from an `Authorization: Bearer` header.
+///
+/// The token is checked with `Rwt::parse_verified` against the secret supplied by the state. A
+/// missing header, a scheme other than `Bearer`, and a token that fails to parse or verify are
+/// all rejected with `401 Unauthorized`. Expiry is your business.
+impl<T, E, S> FromRequestParts<S> for Rwt<T>
+where
+    T: FromStr<Err = E> + Send,
+    E: Debug,
+    S: RwtSecret + Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| strip_bearer(value).ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        Rwt::parse_verified(token, state.rwt_secret()).map_err(|_| StatusCode::UNAUTHORIZED)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::RwtSecret;
+    use crate::Rwt;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use serde::{Deserialize, Serialize};
+    use std::str::FromStr;
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct State;
+
+    impl RwtSecret for State {
+        fn rwt_secret(&self) -> &[u8] {
+            b"secret"
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Payload {
+        sub: String,
+    }
+
+    impl FromStr for Payload {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    async fn whoami(rwt: Rwt<Payload>) -> String {
+        rwt.payload.sub
+    }
+
+    async fn request(authorization: Option<&str>) -> StatusCode {
+        let router = Router::new().route("/", get(whoami)).with_state(State);
+        let mut request = Request::builder().uri("/");
+        if let Some(authorization) = authorization {
+            request = request.header("Authorization", authorization);
+        }
+        let request = request.body(Body::empty()).unwrap();
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    fn token() -> String {
+        let payload = Payload {
+            sub: "jdoe".to_owned(),
+        };
+        Rwt::with_payload(payload, "secret")
+            .unwrap()
+            .encode()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn valid_token_is_extracted() {
+        let authorization = format!("Bearer {}", token());
+        assert_eq!(StatusCode::OK, request(Some(&authorization)).await);
+    }
+
+    #[tokio::test]
+    async fn missing_token_is_unauthorized() {
+        assert_eq!(StatusCode::UNAUTHORIZED, request(None).await);
+        assert_eq!(StatusCode::UNAUTHORIZED, request(Some(&token())).await);
+    }
+
+    #[tokio::test]
+    async fn tampered_token_is_unauthorized() {
+        let body = base64::encode(r#"{"sub":"root"}"#);
+        let (_, signature) = token()
+            .split_once('.')
+            .map(|(b, s)| (b, s.to_owned()))
+            .unwrap();
+        let authorization = format!("Bearer {}.{}", body, signature);
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            request(Some(&authorization)).await
+        );
+    }
+}
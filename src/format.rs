@@ -0,0 +1,310 @@
+use crate::Result;
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The serialization format of a token's payload.
+///
+/// JSON is the default and the only format that goes through `FromStr`. Other formats mark the
+/// body segment of the encoded token so that a verifier knows how to read it.
+///
+/// Which formats there are depends on the features turned on anywhere in the build, so a `match`
+/// outside this crate needs a wildcard arm.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum Format {
+    #[default]
+    Json,
+    /// JSON with sorted keys and no insignificant whitespace. The encoded body is prefixed with
+    /// `!`.
+    ///
+    /// A payload gets the same signature however its fields or entries happen to be ordered, which
+    /// is what you want for anything backed by a `HashMap`. Everything that reads JSON reads this
+    /// too.
+    #[cfg(feature = "canonical")]
+    CanonicalJson,
+    /// MessagePack, via `rmp-serde`. The encoded body is prefixed with `~`.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+/// A payload that can be signed.
+///
+/// With the `serde` feature (on by default), that's anything `Serialize`. Without it, a payload
+/// is its own bytes, so anything `AsRef<[u8]>` will do: a `String`, say, or a `Vec<u8>`.
+pub trait Signable {
+    #[doc(hidden)]
+    fn to_bytes(&self, format: Format) -> Result<Vec<u8>>;
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize + ?Sized> Signable for T {
+    fn to_bytes(&self, format: Format) -> Result<Vec<u8>> {
+        match format {
+            Format::Json => Ok(serde_json::to_vec(self)?),
+            #[cfg(feature = "canonical")]
+            Format::CanonicalJson => {
+                Ok(serde_json::to_vec(&sort_keys(serde_json::to_value(self)?))?)
+            }
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => Ok(rmp_serde::to_vec_named(self)?),
+        }
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+impl<T: AsRef<[u8]> + ?Sized> Signable for T {
+    fn to_bytes(&self, _format: Format) -> Result<Vec<u8>> {
+        Ok(self.as_ref().to_vec())
+    }
+}
+
+/// Sort every object in `value` by key, however deep.
+///
+/// serde_json's `Map` happens to sort its keys, but not if anything in the build turns on its
+/// `preserve_order` feature, which keeps them in insertion order instead. So the keys are sorted
+/// here, and the map rebuilt in that order, which comes to the same thing for either kind of map.
+#[cfg(feature = "canonical")]
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    use alloc::collections::BTreeMap;
+    use alloc::string::String;
+    use serde_json::Value;
+
+    match value {
+        Value::Object(map) => {
+            let sorted: BTreeMap<String, Value> =
+                map.into_iter().map(|(k, v)| (k, sort_keys(v))).collect();
+            Value::Object(sorted.into_iter().collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sort_keys).collect()),
+        value => value,
+    }
+}
+
+impl Format {
+    pub(crate) fn serialize<T: Signable + ?Sized>(self, payload: &T) -> Result<Vec<u8>> {
+        payload.to_bytes(self)
+    }
+
+    /// Whether the payload can be read as JSON.
+    pub(crate) fn is_json(self) -> bool {
+        match self {
+            Format::Json => true,
+            #[cfg(feature = "canonical")]
+            Format::CanonicalJson => true,
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => false,
+        }
+    }
+
+    /// The marker written ahead of the body segment.
+    ///
+    /// Neither `~` nor `!` appears in either base64 alphabet, so a marker can't be mistaken for
+    /// part of a body.
+    pub(crate) fn prefix(self) -> &'static str {
+        match self {
+            Format::Json => "",
+            #[cfg(feature = "canonical")]
+            Format::CanonicalJson => "!",
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => "~",
+        }
+    }
+
+    /// Split the marker (if any) off of a body segment.
+    pub(crate) fn detect(body: &str) -> (Format, &str) {
+        #[cfg(feature = "canonical")]
+        {
+            if let Some(body) = body.strip_prefix('!') {
+                return (Format::CanonicalJson, body);
+            }
+        }
+        #[cfg(feature = "msgpack")]
+        {
+            if let Some(body) = body.strip_prefix('~') {
+                return (Format::MessagePack, body);
+            }
+        }
+        (Format::Json, body)
+    }
+}
+
+/// Deserialize a JSON body which the caller is done with.
+///
+/// With the `simd-json` feature, this is where `simd-json` comes in: it parses in place, which is
+/// why it wants the body by value. Payloads are still serialized by `serde_json` either way, so
+/// the bytes a token is signed over don't depend on the backend, and neither do the errors; a
+/// bad body is `Error::Json` whichever parser found it.
+#[cfg(all(feature = "serde", not(feature = "simd-json")))]
+pub(crate) fn from_json<T: DeserializeOwned>(body: Vec<u8>) -> Result<T> {
+    Ok(serde_json::from_slice(&body)?)
+}
+
+#[cfg(feature = "simd-json")]
+pub(crate) fn from_json<T: DeserializeOwned>(mut body: Vec<u8>) -> Result<T> {
+    use serde::de::Error as _;
+
+    simd_json::serde::from_slice(&mut body).map_err(|e| serde_json::Error::custom(e).into())
+}
+
+#[cfg(all(test, feature = "simd-json"))]
+mod simd_tests {
+    use crate::{RawPayload, Rwt};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        sub: String,
+        roles: Vec<String>,
+        exp: i64,
+        ratio: f64,
+        note: Option<String>,
+    }
+
+    fn payload() -> Payload {
+        Payload {
+            sub: "jdoe \"the\" \u{e9}l\u{e8}ve\n".into(),
+            roles: vec!["admin".into(), "ops".into()],
+            exp: 13,
+            ratio: 0.1,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn backends_agree_on_signed_bytes() {
+        let payload = payload();
+        let serde_json = serde_json::to_vec(&payload).unwrap();
+        let simd_json = simd_json::serde::to_vec(&payload).unwrap();
+        assert_eq!(serde_json, simd_json);
+
+        // Signed over simd-json's bytes, read back with simd-json, and checked against a body
+        // re-serialized by serde_json.
+        let signed = Rwt::<RawPayload>::with_raw_payload(&simd_json, "secret").unwrap();
+        let parsed = Rwt::<Payload>::from_json_str(&signed.encode().unwrap()).unwrap();
+        assert_eq!(payload, parsed.payload);
+        assert!(parsed.is_valid("secret"));
+
+        // And the other way around.
+        let encoded = Rwt::with_payload(&payload, "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+        let parsed = Rwt::<RawPayload>::parse_verified(&encoded, "secret").unwrap();
+        assert_eq!(simd_json, parsed.payload.as_str().as_bytes());
+    }
+
+    #[test]
+    fn simd_json_errors_are_json_errors() {
+        let encoded = Rwt::with_payload("not a payload", "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+        match Rwt::<Payload>::from_json_str(&encoded) {
+            Err(crate::Error::Json(_)) => (),
+            other => panic!("expected Json error, got {:?}", other),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "canonical"))]
+mod tests {
+    use super::Format;
+    use crate::Rwt;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        limits: HashMap<String, u32>,
+    }
+
+    impl FromStr for Payload {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    fn payload() -> Payload {
+        let limits = (0..32).map(|i| (format!("limit-{}", i), i)).collect();
+        Payload { limits }
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys() {
+        #[derive(Serialize)]
+        struct Unsorted {
+            b: u8,
+            a: u8,
+        }
+
+        let body = Format::CanonicalJson
+            .serialize(&Unsorted { b: 2, a: 1 })
+            .unwrap();
+        assert_eq!(br#"{"a":1,"b":2}"#, &body[..]);
+    }
+
+    #[test]
+    fn canonical_json_sorts_nested_keys() {
+        // Written out of order, which is the order a `preserve_order` map would keep them in.
+        let value = serde_json::json!({
+            "z": [{"y": 1, "x": 2}],
+            "b": {"d": null, "c": {"f": true, "e": false}},
+            "a": "a",
+        });
+        let body = Format::CanonicalJson.serialize(&value).unwrap();
+        assert_eq!(
+            br#"{"a":"a","b":{"c":{"e":false,"f":true},"d":null},"z":[{"x":2,"y":1}]}"#,
+            &body[..]
+        );
+
+        let sorted = super::sort_keys(value);
+        let keys: Vec<_> = sorted.as_object().unwrap().keys().collect();
+        assert_eq!(vec!["a", "b", "z"], keys);
+    }
+
+    #[test]
+    fn canonical_hash_map_verifies_reliably() {
+        for _ in 0..20 {
+            let rwt = Rwt::builder()
+                .payload(payload())
+                .format(Format::CanonicalJson)
+                .sign("secret")
+                .unwrap();
+            let encoded = rwt.encode().unwrap();
+            assert!(encoded.starts_with('!'));
+
+            // A fresh map iterates in a fresh order.
+            let parsed: Rwt<Payload> = encoded.parse().unwrap();
+            assert_eq!(rwt, parsed);
+            assert!(parsed.is_valid("secret"));
+            assert!(Rwt::<Payload>::parse_verified(&encoded, "secret").is_ok());
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "serde")))]
+mod tests {
+    use crate::Rwt;
+
+    #[test]
+    fn sign_string_without_serde() {
+        let payload = String::from("my payload");
+        let rwt = Rwt::with_payload(payload, "secret").unwrap();
+        assert!(rwt.is_valid("secret"));
+
+        let encoded = rwt.encode().unwrap();
+        assert_eq!(
+            base64::encode("my payload"),
+            encoded.split('.').next().unwrap()
+        );
+
+        let parsed = Rwt::<String>::parse_verified(&encoded, "secret").unwrap();
+        assert_eq!(rwt, parsed);
+        assert_eq!("my payload", parsed.payload);
+    }
+}
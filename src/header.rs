@@ -0,0 +1,294 @@
+use crate::{signing_input, Algorithm, Base64Codec, Error, Result};
+use alloc::borrow::{Cow, ToOwned};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Marks a leading segment as a full header rather than a bare key id.
+pub(crate) const MARKER: char = '$';
+
+/// Leads the signing input of a token with a full header.
+///
+/// A key id goes into the signing input behind its length, and no key id is `u64::MAX` bytes long,
+/// so this can never be mistaken for one.
+const TAG: [u8; 8] = [0xff; 8];
+
+/// What a token says about itself: how it was signed, what it is, and which key signed it.
+///
+/// Every token has one. A header holding nothing more than an algorithm and (maybe) a key id is
+/// written as tokens always have been, the algorithm left off and the key id in a segment of its
+/// own, so `Header::default()` makes the same token `with_payload` does. Anything more, like a
+/// `typ`, and the whole header goes in the leading segment as JSON. Either way, it's covered by
+/// the signature.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Header {
+    pub alg: Algorithm,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub typ: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub kid: Option<String>,
+}
+
+impl Header {
+    pub(crate) fn new(alg: Algorithm, kid: Option<String>) -> Header {
+        Header {
+            alg,
+            typ: None,
+            kid,
+        }
+    }
+
+    /// Whether the header can be written as a bare key id (or not at all).
+    ///
+    /// An unsigned token always says so in a full header, never leaving it to the reader's
+    /// default.
+    pub(crate) fn is_compact(&self) -> bool {
+        self.typ.is_none() && self.alg != Algorithm::None
+    }
+
+    /// The exact bytes the signature covers, given the serialized payload.
+    ///
+    /// A compact header can't carry an empty key id, which would encode as an empty segment that
+    /// no parser accepts, so that's `Error::Format` rather than a token nobody can read.
+    pub(crate) fn signing_input<'a>(&self, body: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        if self.is_compact() {
+            if self.kid.as_deref() == Some("") {
+                return Err(Error::Format("Empty key id".into()));
+            }
+            return Ok(signing_input(self.kid.as_deref(), body));
+        }
+
+        let header = self.to_json()?;
+        let mut input = Vec::with_capacity(16 + header.len() + body.len());
+        input.extend_from_slice(&TAG);
+        input.extend_from_slice(&(header.len() as u64).to_be_bytes());
+        input.extend_from_slice(&header);
+        input.extend_from_slice(body);
+        Ok(Cow::Owned(input))
+    }
+
+    /// Encode the leading segment of the token, if it has one.
+    pub(crate) fn encode(&self, codec: impl Base64Codec) -> Result<Option<String>> {
+        if self.is_compact() {
+            return Ok(self.kid.as_ref().map(|kid| codec.encode(kid.as_bytes())));
+        }
+        Ok(Some(format!(
+            "{}{}",
+            MARKER,
+            codec.encode(&self.to_json()?)
+        )))
+    }
+
+    /// Decode the leading segment of a token.
+    ///
+    /// A full header names its own algorithm. Otherwise, the token gets `alg`.
+    pub(crate) fn decode(
+        segment: Option<&str>,
+        codec: impl Base64Codec,
+        alg: Algorithm,
+    ) -> Result<Header> {
+        match segment {
+            None => Ok(Header::new(alg, None)),
+            Some(segment) => match segment.strip_prefix(MARKER) {
+                Some(header) => Header::from_json(&codec.decode(header)?),
+                None => {
+                    let kid = codec.decode(segment)?;
+                    let kid = core::str::from_utf8(&kid)?.to_owned();
+                    Ok(Header::new(alg, Some(kid)))
+                }
+            },
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    #[cfg(feature = "serde")]
+    fn from_json(json: &[u8]) -> Result<Header> {
+        Ok(serde_json::from_slice(json)?)
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn to_json(&self) -> Result<Vec<u8>> {
+        Err(Error::Format(
+            "Full headers require the serde feature".into(),
+        ))
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn from_json(_json: &[u8]) -> Result<Header> {
+        Err(Error::Format(
+            "Full headers require the serde feature".into(),
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::Header;
+    use crate::{Algorithm, Error, Hs512, Rwt};
+    use serde::{Deserialize, Serialize};
+    use std::str::FromStr;
+
+    #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        jti: String,
+    }
+
+    impl FromStr for Payload {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    fn payload() -> Payload {
+        Payload {
+            jti: "this one".into(),
+        }
+    }
+
+    fn full_header() -> Header {
+        Header {
+            alg: Algorithm::HmacSha512,
+            typ: Some("at+rwt".into()),
+            kid: Some("k1".into()),
+        }
+    }
+
+    /// Swap the header segment of `token` for `header`, keeping the body and signature.
+    fn with_forged_header(token: &str, header: &Header) -> String {
+        let (_, rest) = token.split_once('.').unwrap();
+        let header = base64::encode(serde_json::to_vec(header).unwrap());
+        format!("${}.{}", header, rest)
+    }
+
+    #[test]
+    fn default_header_round_trips() {
+        let rwt = Rwt::with_header(payload(), Header::default(), "secret").unwrap();
+        let plain = Rwt::with_payload(payload(), "secret").unwrap();
+        assert_eq!(plain.encode().unwrap(), rwt.encode().unwrap());
+
+        let parsed = Rwt::<Payload>::parse_verified(&rwt.encode().unwrap(), "secret").unwrap();
+        assert_eq!(&Header::default(), parsed.header());
+    }
+
+    #[test]
+    fn full_header_round_trips() {
+        let rwt = Rwt::with_header(payload(), full_header(), "secret").unwrap();
+        let encoded = rwt.encode().unwrap();
+        assert!(encoded.starts_with('$'));
+
+        let parsed: Rwt<Payload> = encoded.parse().unwrap();
+        assert_eq!(&full_header(), parsed.header());
+        assert_eq!(Some("k1"), parsed.key_id());
+
+        // The header names SHA512, but an `Rwt<Payload>` is checked with SHA256 all the same.
+        assert!(!parsed.is_valid("secret"));
+        assert!(parsed
+            .verify_with_alg("secret", Algorithm::HmacSha512)
+            .is_ok());
+
+        let typed: Rwt<Payload, Hs512> = encoded.parse().unwrap();
+        assert!(typed.is_valid("secret"));
+
+        let verified =
+            Rwt::<Payload>::parse_verified_alg(&encoded, "secret", Algorithm::HmacSha512).unwrap();
+        assert_eq!(rwt, verified);
+    }
+
+    #[test]
+    fn header_tampering_invalidates_token() {
+        let encoded = Rwt::with_header(payload(), full_header(), "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+
+        let forgeries = [
+            Header {
+                typ: Some("refresh+rwt".into()),
+                ..full_header()
+            },
+            Header {
+                kid: Some("k2".into()),
+                ..full_header()
+            },
+            Header {
+                kid: None,
+                ..full_header()
+            },
+        ];
+        for header in &forgeries {
+            let forged = with_forged_header(&encoded, header);
+            assert!(!forged.parse::<Rwt<Payload>>().unwrap().is_valid("secret"));
+            match Rwt::<Payload>::parse_verified_alg(&forged, "secret", Algorithm::HmacSha512) {
+                Err(Error::InvalidSignature) => (),
+                other => panic!("expected InvalidSignature, got {:?}", other),
+            }
+        }
+
+        let downgraded = with_forged_header(
+            &encoded,
+            &Header {
+                alg: Algorithm::HmacSha256,
+                ..full_header()
+            },
+        );
+        match Rwt::<Payload>::parse_verified(&downgraded, "secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_with_alg_ignores_claimed_algorithm() {
+        let rwt = Rwt::with_header(payload(), full_header(), "secret").unwrap();
+        assert!(rwt.verify_with_alg("secret", Algorithm::HmacSha512).is_ok());
+        match rwt.verify_with_alg("secret", Algorithm::HmacSha256) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+
+        // Signed with SHA512, but claiming SHA256.
+        let forged = with_forged_header(
+            &rwt.encode().unwrap(),
+            &Header {
+                alg: Algorithm::HmacSha256,
+                ..full_header()
+            },
+        );
+        let forged: Rwt<Payload> = forged.parse().unwrap();
+        assert_eq!(Algorithm::HmacSha256, forged.algorithm());
+        match forged.verify_with_alg("secret", Algorithm::HmacSha256) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn full_header_is_not_a_key_id() {
+        let rwt = Rwt::with_header(payload(), full_header(), "secret").unwrap();
+        let encoded = rwt.encode().unwrap();
+        let (header, rest) = encoded.split_once('.').unwrap();
+
+        // The same header bytes, passed off as a key id.
+        let kid = base64::decode(&header[1..]).unwrap();
+        let forged = format!("{}.{}", base64::encode(kid), rest);
+        match Rwt::<Payload>::parse_verified_alg(&forged, "secret", Algorithm::HmacSha512) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+}
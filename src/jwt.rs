@@ -0,0 +1,226 @@
+use crate::error::with_input;
+use crate::{
+    check_signature, json, parse_payload, Algorithm, Error, Format, Header, Result, Rwt,
+    SigningAlgorithm,
+};
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Debug;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// The JOSE header of a JWT, or as much of it as we care about.
+#[derive(Serialize, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+}
+
+impl Algorithm {
+    /// The name JWT uses for this algorithm, if JWT supports it.
+    fn jwt_name(self) -> Option<&'static str> {
+        match self {
+            Algorithm::HmacSha256 => Some("HS256"),
+            Algorithm::HmacSha384 => Some("HS384"),
+            Algorithm::HmacSha512 => Some("HS512"),
+            #[cfg(feature = "ed25519")]
+            Algorithm::Ed25519 => None,
+            #[cfg(feature = "blake3")]
+            Algorithm::Blake3Keyed => None,
+            Algorithm::None => None,
+        }
+    }
+
+    fn from_jwt_name(name: &str) -> Option<Algorithm> {
+        match name {
+            "HS256" => Some(Algorithm::HmacSha256),
+            "HS384" => Some(Algorithm::HmacSha384),
+            "HS512" => Some(Algorithm::HmacSha512),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Serialize, A> Rwt<T, A> {
+    /// Encode the token as a JWT.
+    ///
+    /// The result is the usual three-segment `header.payload.signature`, in unpadded URL-safe
+    /// base64, with a header along the lines of `{"alg":"HS256","typ":"JWT"}`. A key id, if
+    /// present, goes in the header as `kid`. Any JWT library holding the secret can read it.
+    ///
+    /// JWT signs the encoded header and payload rather than the payload alone, so the token's own
+    /// signature can't be reused. That's why this needs the secret: the token is signed again.
+    /// Only the HMAC algorithms are supported.
+    #[must_use = "encoding returns the token and changes nothing else"]
+    pub fn encode_jwt<S: AsRef<[u8]>>(&self, secret: S) -> Result<String> {
+        let algorithm = self.header.alg;
+        let alg = algorithm.jwt_name().ok_or_else(|| {
+            Error::Format(format!("{:?} is not supported in JWT mode", algorithm))
+        })?;
+        let header = JwtHeader {
+            alg: alg.to_owned(),
+            typ: Some("JWT".to_owned()),
+            kid: self.header.kid.clone(),
+        };
+
+        let header = base64::encode_config(json::to_vec(&header)?, base64::URL_SAFE_NO_PAD);
+        let payload = base64::encode_config(json::to_vec(&self.payload)?, base64::URL_SAFE_NO_PAD);
+        let input = format!("{}.{}", header, payload);
+        let signature = algorithm.sign(input.as_bytes(), secret.as_ref())?;
+        let signature = base64::encode_config(base64::decode(signature)?, base64::URL_SAFE_NO_PAD);
+        Ok(format!("{}.{}", input, signature))
+    }
+}
+
+impl<T, E, A> Rwt<T, A>
+where
+    A: SigningAlgorithm,
+    E: Debug,
+    T: FromStr<Err = E> + Serialize,
+{
+    /// Parse and verify a JWT.
+    ///
+    /// The signature is checked over `header.payload`, exactly as JWT specifies, using the
+    /// algorithm named by the token's type: `HS256` for a plain `Rwt<T>`, `HS512` for an
+    /// `Rwt<T, Hs512>`, and so on. The JWT's own `alg` has to agree, and a JWT naming some other
+    /// algorithm is rejected with `Error::InvalidSignature`; one naming an algorithm JWT mode
+    /// doesn't support at all (`none` in particular) is rejected with `Error::Format`.
+    ///
+    /// The resulting token is an ordinary `Rwt`, signed afresh with the same secret and algorithm,
+    /// so `is_valid` and `encode` work on it as usual. A `kid` in the header becomes its key id.
+    pub fn parse_jwt<S: AsRef<[u8]>>(s: &str, secret: S) -> Result<Rwt<T, A>> {
+        let mut parts = s.split('.');
+        let (header, payload, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(header), Some(payload), Some(signature)) if parts.next().is_none() => {
+                (header, payload, signature)
+            }
+            _ => return Err(Error::Format(with_input("Not a JWT", s))),
+        };
+
+        let header: JwtHeader =
+            json::from_slice(&base64::decode_config(header, base64::URL_SAFE_NO_PAD)?)?;
+        let algorithm = Algorithm::from_jwt_name(&header.alg)
+            .ok_or_else(|| Error::Format(with_input("Unsupported JWT algorithm", &header.alg)))?;
+        if algorithm != A::ALGORITHM {
+            return Err(Error::InvalidSignature);
+        }
+
+        let secret = secret.as_ref();
+        let input = &s[..header_and_payload_len(s)];
+        let signature = base64::encode(base64::decode_config(signature, base64::URL_SAFE_NO_PAD)?);
+        check_signature(algorithm, input.as_bytes(), &signature, secret)?;
+
+        let payload = parse_payload(&base64::decode_config(payload, base64::URL_SAFE_NO_PAD)?)?;
+        Rwt::sign(
+            payload,
+            Header::new(algorithm, header.kid),
+            Format::Json,
+            secret,
+        )
+    }
+}
+
+/// The length of the `header.payload` prefix of a JWT.
+fn header_and_payload_len(s: &str) -> usize {
+    s.rfind('.').unwrap_or(s.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, Hs512, Rwt};
+    use serde::{Deserialize, Serialize};
+    use std::str::FromStr;
+
+    /// The example token from jwt.io.
+    const JWT: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+                       eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ.\
+                       SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c";
+    const SECRET: &str = "your-256-bit-secret";
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        sub: String,
+        name: String,
+        iat: i64,
+    }
+
+    impl FromStr for Payload {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    fn payload() -> Payload {
+        Payload {
+            sub: "1234567890".to_owned(),
+            name: "John Doe".to_owned(),
+            iat: 1516239022,
+        }
+    }
+
+    #[test]
+    fn parse_jwt() {
+        let rwt = Rwt::<Payload>::parse_jwt(JWT, SECRET).unwrap();
+        assert_eq!(payload(), rwt.payload);
+        assert!(rwt.is_valid(SECRET));
+    }
+
+    #[test]
+    fn encode_jwt() {
+        let rwt = Rwt::with_payload(payload(), SECRET).unwrap();
+        assert_eq!(JWT, rwt.encode_jwt(SECRET).unwrap());
+    }
+
+    #[test]
+    fn jwt_round_trip_with_key_id() {
+        let rwt = Rwt::with_payload_kid(payload(), SECRET, "2020-05").unwrap();
+        let jwt = rwt.encode_jwt(SECRET).unwrap();
+        let parsed = Rwt::<Payload>::parse_jwt(&jwt, SECRET).unwrap();
+        assert_eq!(rwt, parsed);
+    }
+
+    #[test]
+    fn parse_jwt_with_wrong_secret() {
+        match Rwt::<Payload>::parse_jwt(JWT, "secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_jwt_rejects_unexpected_algorithm() {
+        let rwt = Rwt::<_, Hs512>::with_payload_typed(payload(), SECRET).unwrap();
+        let jwt = rwt.encode_jwt(SECRET).unwrap();
+        match Rwt::<Payload>::parse_jwt(&jwt, SECRET) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+
+        let parsed = Rwt::<Payload, Hs512>::parse_jwt(&jwt, SECRET).unwrap();
+        assert!(parsed.is_valid(SECRET));
+        assert_eq!(rwt, parsed);
+
+        // And the other way around: an HS256 JWT is no good to an HS512 parser.
+        match Rwt::<Payload, Hs512>::parse_jwt(JWT, SECRET) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_jwt_rejects_alg_none() {
+        let (_, rest) = JWT.split_once('.').unwrap();
+        let header = base64::encode_config(r#"{"alg":"none"}"#, base64::URL_SAFE_NO_PAD);
+        let jwt = format!("{}.{}", header, rest);
+        match Rwt::<Payload>::parse_jwt(&jwt, SECRET) {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+    }
+}
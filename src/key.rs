@@ -0,0 +1,122 @@
+use crate::Result;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An HMAC secret.
+///
+/// Anything `AsRef<[u8]>` will do as a secret, which makes it easy to sign with the wrong string.
+/// A `SecretKey` can only be a secret. It works everywhere a secret is accepted, zeroes its bytes
+/// when dropped, and won't print them.
+///
+/// If you don't already have a secret, the `rand` feature's `SecretKey::generate` will make you
+/// a good one. Store it with `to_base64` and read it back with `from_base64`.
+#[derive(Clone)]
+pub struct SecretKey(Vec<u8>);
+
+impl SecretKey {
+    pub fn new(bytes: impl Into<Vec<u8>>) -> SecretKey {
+        SecretKey(bytes.into())
+    }
+
+    /// Generate a random 32-byte key from the operating system's CSPRNG.
+    #[cfg(feature = "rand")]
+    pub fn generate() -> SecretKey {
+        use rand::RngCore;
+
+        let mut bytes = alloc::vec![0; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        SecretKey(bytes)
+    }
+
+    /// Decode a key from standard base64, padded or not.
+    pub fn from_base64(s: &str) -> Result<SecretKey> {
+        Ok(SecretKey(base64::decode(s)?))
+    }
+
+    /// Encode the key as standard base64, for storage.
+    ///
+    /// The result is as secret as the key itself.
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.0)
+    }
+}
+
+impl AsRef<[u8]> for SecretKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut self.0);
+
+        // Without zeroize, this is best effort: black_box discourages the compiler from deciding
+        // that nobody will ever read the zeroes and skipping them.
+        #[cfg(not(feature = "zeroize"))]
+        {
+            self.0.iter_mut().for_each(|b| *b = 0);
+            core::hint::black_box(&self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecretKey;
+    use crate::{Error, Rwt};
+
+    #[test]
+    fn secret_key_signs_like_bytes() {
+        let key = SecretKey::new(&b"secret"[..]);
+        let rwt = Rwt::with_payload("payload", &key).unwrap();
+        assert_eq!(Rwt::with_payload("payload", "secret").unwrap(), rwt);
+        assert!(rwt.is_valid(&key));
+        assert!(rwt.is_valid("secret"));
+    }
+
+    #[test]
+    fn secret_key_from_base64() {
+        let key = SecretKey::from_base64("c2VjcmV0").unwrap();
+        assert_eq!(b"secret", key.as_ref());
+        match SecretKey::from_base64("not base64!") {
+            Err(Error::Base64(_)) => (),
+            other => panic!("expected Base64 error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn secret_key_base64_round_trip() {
+        let key = SecretKey::new(&b"secret"[..]);
+        assert_eq!("c2VjcmV0", key.to_base64());
+        let decoded = SecretKey::from_base64(&key.to_base64()).unwrap();
+        assert_eq!(key.as_ref(), decoded.as_ref());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn generated_keys_are_distinct() {
+        let a = SecretKey::generate();
+        let b = SecretKey::generate();
+        assert_eq!(32, a.as_ref().len());
+        assert_ne!(a.as_ref(), b.as_ref());
+
+        let decoded = SecretKey::from_base64(&a.to_base64()).unwrap();
+        assert_eq!(a.as_ref(), decoded.as_ref());
+        assert_eq!("SecretKey(..)", format!("{:?}", a));
+    }
+
+    #[test]
+    fn secret_key_does_not_print() {
+        let key = SecretKey::new(&b"secret"[..]);
+        assert_eq!("SecretKey(..)", format!("{:?}", key));
+    }
+}
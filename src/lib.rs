@@ -1,54 +1,638 @@
+//! Rebel Web Tokens.
+//!
+//! The crate is `no_std` (with `alloc`) when the default `std` feature is disabled. Everything
+//! survives the trip except the `std::error::Error` impl for `Error`; `FromStr` and `Display`
+//! come from `core` and work either way.
+//!
+//! Payloads go through serde (and JSON) courtesy of the default `serde` feature. Without it, the
+//! crate drops serde entirely, and a payload is simply its own bytes; see `Signable`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+mod algorithm;
+#[cfg(feature = "http")]
+mod bearer;
+mod builder;
+mod cache;
+#[cfg(feature = "claims")]
+mod claims;
+mod codec;
+#[cfg(feature = "chrono")]
+mod datetime;
+#[cfg(feature = "ed25519")]
+mod ed25519;
 mod error;
+#[cfg(feature = "axum")]
+mod extract;
+mod format;
+mod header;
+#[cfg(feature = "serde")]
+mod jwt;
+#[cfg(feature = "hkdf")]
+mod kdf;
+mod key;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+#[cfg(feature = "async")]
+mod offload;
+#[cfg(feature = "owned")]
+mod owned;
+pub mod prelude;
+#[cfg(feature = "serde")]
+mod raw;
+#[cfg(feature = "serde")]
+pub mod serde_compact;
+#[cfg(feature = "serde")]
+mod string;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod time;
+mod verified;
 
-use crypto::digest::Digest;
-use crypto::hmac::Hmac;
-use crypto::mac::Mac;
-use crypto::sha2::Sha256;
+use alloc::borrow::{Cow, ToOwned};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use base64::display::Base64Display;
+use core::convert::TryFrom;
+use core::fmt::{self, Display};
+use core::marker::PhantomData;
+use core::str::FromStr;
+use error::with_input;
+use hmac::digest::core_api::BlockSizeUser;
+use hmac::digest::Digest;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
 use serde_json as json;
-use std::fmt::Display;
-use std::str::FromStr;
+use subtle::ConstantTimeEq;
 
+#[cfg(feature = "ed25519")]
+pub use algorithm::Ed25519;
+pub use algorithm::{Algorithm, Hs256, Hs384, Hs512, SigningAlgorithm};
+pub use builder::RwtBuilder;
+pub use cache::TokenCache;
+#[cfg(feature = "claims")]
+pub use claims::{Audience, Claims};
+pub use codec::Base64Codec;
 pub use error::Error;
+#[cfg(feature = "axum")]
+pub use extract::RwtSecret;
+pub use format::{Format, Signable};
+pub use header::Header;
+#[cfg(feature = "hkdf")]
+pub use kdf::derive_key;
+pub use key::SecretKey;
+#[cfg(feature = "owned")]
+pub use owned::OwnedRwt;
+#[cfg(feature = "serde")]
+pub use raw::RawPayload;
+#[cfg(feature = "serde")]
+pub use string::RwtString;
+#[cfg(feature = "std")]
+pub use time::SystemClock;
+pub use time::{
+    ByExpiry, Clock, Expiring, ExpiringMut, FixedClock, IssuedAt, IssuedAtMut, NotBefore,
+};
+pub use verified::VerifiedToken;
 
-pub type Result<T, E = error::Error> = std::result::Result<T, E>;
+pub type Result<T, E = error::Error> = core::result::Result<T, E>;
 
-/// Decode base64 into a string.
+/// The shortest tag, in bytes, that `Rwt::with_payload_truncated` will produce.
+///
+/// Ten bytes is eighty bits, which is about as far as anyone should trim a MAC.
+pub const MIN_TAG_LEN: usize = 10;
+
+/// Decode the body of a token into a string.
 ///
 /// Useful for converting incoming base64 tokens to json before deserializing. It is now necessary
 /// to do this, as far as I can tell, because serde now supports deserializing to a struct that
-/// only borrows the data it represents instead of owning it.
-pub fn decode_base64(s: &str) -> Option<String> {
-    let start_idx = match s.find('.').map(|idx| idx + 1) {
-        None => return None,
-        Some(idx) => idx,
+/// only borrows the data it represents instead of owning it. (See also `Rwt::parse_borrowed`.)
+///
+/// This does not check the signature.
+pub fn decode_body(s: &str) -> Result<String> {
+    let body = base64::STANDARD.decode(split_parts(s)?.body)?;
+    Ok(core::str::from_utf8(&body)?.to_owned())
+}
+
+/// Split a token into its body and signature, still encoded.
+///
+/// Both are slices of `s`; nothing is decoded or copied. A token with no signature (or no
+/// separator to put it behind) is rejected with `Error::Format`. The key id or header segment, if
+/// there is one, is left off, as is any version tag or format marker, so the body is ready for
+/// `base64::decode`. This does not check the signature.
+pub fn parts(s: &str) -> Result<(&str, &str)> {
+    let segments = split_parts(s)?;
+    Ok((segments.body, segments.signature))
+}
+
+/// Decode the body segment of a token straight into a payload.
+///
+/// This is peeking at the claims, as JWT debuggers do: give it the body from `parts`, or from
+/// `Rwt::encode_payload_only`, and it hands back the payload. **This does not check the
+/// signature**, which it never sees. Errors are those of parsing any other token's body:
+/// `Error::Base64`, `Error::Encoding`, or `Error::FromStr`.
+pub fn decode_payload<T, E>(body: &str) -> Result<T>
+where
+    E: fmt::Debug,
+    T: FromStr<Err = E>,
+{
+    parse_payload(&base64::STANDARD.decode(body)?)
+}
+
+/// Decode the signature of a token into the raw bytes of the MAC.
+pub fn decode_signature(s: &str) -> Result<Vec<u8>> {
+    base64::STANDARD.decode(split_parts(s)?.signature)
+}
+
+/// Check a raw signature, computed elsewhere, against a payload.
+///
+/// The signature is re-derived with the default algorithm, `Algorithm::HmacSha256`, exactly as
+/// `Rwt::with_payload` would derive it, and compared in constant time. No token required.
+#[must_use = "verification result must be checked"]
+pub fn verify_signature<T, S>(payload: &T, signature: &[u8], secret: S) -> bool
+where
+    T: Signable,
+    S: AsRef<[u8]>,
+{
+    let body = match Format::Json.serialize(payload) {
+        Err(_) => return false,
+        Ok(body) => body,
     };
 
-    let s = &s[start_idx..];
-    base64::decode(s)
-        .ok()
-        .and_then(|bytes| String::from_utf8(bytes).ok())
+    let signature = base64::encode(signature);
+    check_signature(Algorithm::default(), &body, &signature, secret.as_ref()).is_ok()
+}
+
+/// Verify an encoded token without parsing its payload.
+///
+/// This is for the likes of a gateway, which has to authenticate a token before passing it along
+/// but has no use for what's inside. The body is decoded and hashed, exactly as the signer hashed
+/// it, but never deserialized. Errors are those of `Rwt::parse_verified`, minus anything to do with
+/// the payload itself.
+///
+/// The token has to be signed with the default algorithm, `Algorithm::HmacSha256`, as with
+/// `Rwt::parse_verified`. Use `verify_str_alg` for any other.
+#[must_use = "verification result must be checked"]
+pub fn verify_str<S: AsRef<[u8]>>(token: &str, secret: S) -> Result<()> {
+    verify_str_alg(token, secret, Algorithm::default())
+}
+
+/// Verify an encoded token without parsing its payload, using the algorithm you expect.
+///
+/// This is `verify_str` for tokens signed with some other algorithm. A token whose header names
+/// anything but `expected` is rejected with `Error::InvalidSignature`.
+#[must_use = "verification result must be checked"]
+pub fn verify_str_alg<S: AsRef<[u8]>>(token: &str, secret: S, expected: Algorithm) -> Result<()> {
+    traced(verify_str_untraced(token, secret.as_ref(), expected))
+}
+
+fn verify_str_untraced(token: &str, secret: &[u8], expected: Algorithm) -> Result<()> {
+    let segments = split_parts(token)?;
+    let header = segments.decode_header(base64::STANDARD, expected)?;
+    if header.alg != expected {
+        return Err(Error::InvalidSignature);
+    }
+    let body = base64::STANDARD.decode(segments.body)?;
+    let signature = segments.signature(base64::STANDARD)?;
+    let input = header.signing_input(&body)?;
+    check_signature(expected, &input, &signature, secret)
+}
+
+/// Verify an encoded token and hand back its payload, all in one go.
+///
+/// This is what most callers want from a token: the payload, but only if it's authentic. The
+/// signature is checked (in constant time) before the payload is parsed, and the payload is
+/// never returned otherwise. Errors are those of `Rwt::parse_verified`: `Error::Format` or
+/// `Error::Base64` for something that isn't a token at all, `Error::InvalidSignature` for a
+/// forgery, and `Error::FromStr` for a payload that doesn't parse. Expiry is still your business.
+pub fn verify_and_decode<T, E, S>(token: &str, secret: S) -> Result<T>
+where
+    E: fmt::Debug,
+    T: FromStr<Err = E>,
+    S: AsRef<[u8]>,
+{
+    Rwt::<T>::parse_verified(token, secret).map(|rwt| rwt.payload)
+}
+
+/// Decode base64 into a string.
+///
+/// This used to decode whatever followed the first `.`, which is the signature and not the body.
+/// It now decodes the body, which is what it always claimed to do.
+#[deprecated(
+    since = "0.5.0",
+    note = "use `decode_body` or `decode_signature` instead"
+)]
+pub fn decode_base64(s: &str) -> Option<String> {
+    decode_body(s).ok()
 }
 
 /// Represents a web token.
 ///
 /// For optimal usage, your payload should be any struct implementing `Serialize`, `Deserialize`,
 /// and `FromStr`, but none of these are technically required.
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct Rwt<T> {
+///
+/// The second parameter names the algorithm the token signs and parses with by default; see
+/// `SigningAlgorithm`. Leave it off and you get HMAC-SHA256, as ever.
+///
+/// `Debug` leaves out the signature, which is as good as a password for anyone who wants to
+/// replay the token. Use `debug_full` when you really do want to see it.
+#[derive(Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rwt<T, A = Hs256> {
     pub payload: T,
     signature: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    header: Header,
+    #[cfg_attr(feature = "serde", serde(default))]
+    format: Format,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    marker: PhantomData<A>,
+}
+
+impl<T> Rwt<T> {
+    /// Start building a token.
+    ///
+    /// See `RwtBuilder` for the available options.
+    pub fn builder() -> RwtBuilder<T> {
+        RwtBuilder::new()
+    }
+
+    /// Create a token with no signature at all, for test fixtures and placeholders.
+    ///
+    /// **An unsigned token is never valid.** Its signature is empty, and no secret derives an
+    /// empty signature, so `is_valid` and friends reject it whatever you give them. It won't
+    /// survive a trip through `encode` and `parse`, either, since a token without a signature
+    /// doesn't parse. The payload is yours to read as usual.
+    pub fn new_unsigned(payload: T) -> Rwt<T> {
+        Rwt {
+            payload,
+            signature: String::new(),
+            header: Header::default(),
+            format: Format::default(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Create a token that is deliberately unsigned, for passing between trusted components.
+    ///
+    /// Unlike `new_unsigned`, this makes a real token: its header names `Algorithm::None`, and it
+    /// encodes and parses like any other, with an empty signature. **It is never valid.**
+    /// `verify`, `is_valid`, `parse_verified`, and every other signature check reject it with
+    /// `Error::InvalidSignature` (or `false`), whatever secret you give them. The one way to
+    /// accept it is `verify_unsigned`.
+    pub fn with_payload_unsigned(payload: T) -> Rwt<T> {
+        Rwt {
+            payload,
+            signature: String::new(),
+            header: Header::new(Algorithm::None, None),
+            format: Format::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, A> Rwt<T, A> {
+    /// The token's payload, verified or not.
+    pub fn payload_ref(&self) -> &T {
+        &self.payload
+    }
+
+    /// The id of the key used to sign this token, if it has one.
+    ///
+    /// For a parsed token, this is whatever the token claims. Don't trust it until you've
+    /// verified the signature.
+    pub fn key_id(&self) -> Option<&str> {
+        self.header.kid.as_deref()
+    }
+
+    /// The algorithm the token claims to be signed with.
+    ///
+    /// For a token with a full header, that's the header's say-so, and it's no more trustworthy
+    /// than anything else the token says. Look at it, by all means, but verify with
+    /// `verify_with_alg`.
+    pub fn algorithm(&self) -> Algorithm {
+        self.header.alg
+    }
+
+    /// The token's header.
+    ///
+    /// As with `key_id`, a parsed token's header is whatever the token claims until you've
+    /// verified the signature.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The token's signature, in standard base64.
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Compare two tokens by signature, in constant time.
+    ///
+    /// The derived `PartialEq` is fine for everyday use, but it gives up at the first byte that
+    /// differs, and how long that takes says something about the signatures. Use this wherever
+    /// one of the two tokens is secret. Tokens signed over different inputs (or with different
+    /// algorithms) never compare equal.
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        self.header.alg == other.header.alg && fixed_time_eq(&self.signature, &other.signature)
+    }
+
+    /// The token's signature, decoded to the raw bytes of the MAC.
+    pub fn signature_bytes(&self) -> Result<Vec<u8>> {
+        Ok(base64::decode(&self.signature)?)
+    }
+
+    /// Accept a token made by `with_payload_unsigned`, and only such a token.
+    ///
+    /// **This checks nothing but the token's say-so.** Anyone can make an unsigned token, so call
+    /// this only where everything upstream is trusted. What it won't do is let a signed token
+    /// through: one naming any other algorithm is rejected with `Error::InvalidSignature`, since
+    /// its signature ought to be checked with `verify`. So is a token claiming to be unsigned that
+    /// still carries a signature.
+    #[must_use = "verification result must be checked"]
+    pub fn verify_unsigned(&self) -> Result<()> {
+        if self.header.alg == Algorithm::None && self.signature.is_empty() {
+            Ok(())
+        } else {
+            traced(Err(Error::InvalidSignature))
+        }
+    }
+
+    /// Format the token for debugging, signature included.
+    ///
+    /// The plain `Debug` impl redacts the signature so that tokens can be logged without handing
+    /// them out. This one doesn't; keep it out of the logs.
+    pub fn debug_full(&self) -> impl fmt::Debug + '_
+    where
+        T: fmt::Debug,
+    {
+        struct Full<'a, T, A>(&'a Rwt<T, A>);
+
+        impl<T: fmt::Debug, A> fmt::Debug for Full<'_, T, A> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.debug_fields(f, &self.0.signature)
+            }
+        }
+
+        Full(self)
+    }
+
+    fn debug_fields(&self, f: &mut fmt::Formatter, signature: &dyn fmt::Debug) -> fmt::Result
+    where
+        T: fmt::Debug,
+    {
+        f.debug_struct("Rwt")
+            .field("payload", &self.payload)
+            .field("signature", signature)
+            .field("header", &self.header)
+            .field("format", &self.format)
+            .finish()
+    }
+
+    /// Transform the token's payload and sign the result.
+    ///
+    /// The old signature is thrown away; the new token is signed with `secret`, using the same
+    /// algorithm and key id as the original. Nothing here checks the original, so verify it
+    /// first if it came from somewhere you don't trust.
+    pub fn map_payload<U, F, S>(self, secret: S, f: F) -> Result<Rwt<U, A>>
+    where
+        U: Signable,
+        F: FnOnce(T) -> U,
+        S: AsRef<[u8]>,
+    {
+        let payload = f(self.payload);
+        Rwt::sign(payload, self.header, self.format, secret.as_ref())
+    }
+
+    /// Transform the token's payload with a fallible function and sign the result.
+    ///
+    /// This is `map_payload` for transforms that can fail. An error from `f` comes back as is,
+    /// and so does an error from signing, by way of `From<Error>`.
+    pub fn try_map_payload<U, E, F, S>(self, secret: S, f: F) -> Result<Rwt<U, A>, E>
+    where
+        U: Signable,
+        E: From<Error>,
+        F: FnOnce(T) -> Result<U, E>,
+        S: AsRef<[u8]>,
+    {
+        let payload = f(self.payload)?;
+        Ok(Rwt::sign(
+            payload,
+            self.header,
+            self.format,
+            secret.as_ref(),
+        )?)
+    }
 }
 
-impl<T: Serialize> Rwt<T> {
+impl<T: Signable> Rwt<T> {
     /// Create a web token with the provided payload.
     ///
-    /// This function requires that the payload be `Serialize`.
+    /// This function requires that the payload be `Signable`, which usually means `Serialize`.
+    /// The token is signed with the default algorithm, `Algorithm::HmacSha256`.
     pub fn with_payload<S: AsRef<[u8]>>(payload: T, secret: S) -> Result<Rwt<T>> {
-        let signature = derive_signature(&payload, Sha256::new(), secret.as_ref())?;
+        Rwt::with_payload_alg(payload, secret, Algorithm::default())
+    }
+
+    /// Create a web token with the provided payload, signed using the given algorithm.
+    ///
+    /// The algorithm goes in the token's header, but the token is still an `Rwt<T>`, and
+    /// `is_valid` checks an `Rwt<T>` with HMAC-SHA256. Check this one with `verify_with_alg`, or
+    /// make an `Rwt<T, Hs512>` (say) with `with_payload_typed` instead. The encoded form doesn't
+    /// record the algorithm, either, unless the header has a `typ` and goes along whole (see
+    /// `with_header`), so a verifier parsing the token has to know which algorithm to expect; see
+    /// `Rwt::parse_verified_alg`.
+    pub fn with_payload_alg<S: AsRef<[u8]>>(
+        payload: T,
+        secret: S,
+        algorithm: Algorithm,
+    ) -> Result<Rwt<T>> {
+        let header = Header::new(algorithm, None);
+        Rwt::sign(payload, header, Format::Json, secret.as_ref())
+    }
+
+    /// Create a web token with the provided payload and header.
+    ///
+    /// The payload is signed with the header's algorithm, and the header goes along in the
+    /// encoded form, covered by the signature; see `Header`. A parsed token reports the algorithm
+    /// its full header names, but verification never takes its word for it: `is_valid` uses the
+    /// algorithm named by the token's type, and `verify_with_alg` the one you pass.
+    pub fn with_header<S: AsRef<[u8]>>(payload: T, header: Header, secret: S) -> Result<Rwt<T>> {
+        Rwt::sign(payload, header, Format::Json, secret.as_ref())
+    }
+
+    /// Create a web token with the provided payload, tagged with a key id.
+    ///
+    /// The key id tells a verifier which of its secrets signed the token. It travels in the
+    /// encoded form as an extra leading segment and is covered by the signature, so it can't be
+    /// swapped out for another. An empty key id would make an empty segment, and is
+    /// `Error::Format`.
+    pub fn with_payload_kid<S, K>(payload: T, secret: S, key_id: K) -> Result<Rwt<T>>
+    where
+        S: AsRef<[u8]>,
+        K: Into<String>,
+    {
+        let header = Header::new(Algorithm::default(), Some(key_id.into()));
+        Rwt::sign(payload, header, Format::Json, secret.as_ref())
+    }
+
+    /// Create a web token with the provided payload, signed using HMAC over an arbitrary digest.
+    ///
+    /// This is for digests `Algorithm` doesn't cover. Only the *type* of `digest` matters; pass a
+    /// fresh one (e.g. `Sha512::new()`) and a fresh state is derived from it for each hashing
+    /// pass. The token has no way to record which digest signed it, so check it with
+    /// `is_valid_digest` rather than `is_valid`.
+    pub fn with_payload_digest<D, S>(payload: T, secret: S, _digest: D) -> Result<Rwt<T>>
+    where
+        D: Digest + BlockSizeUser,
+        S: AsRef<[u8]>,
+    {
+        let input = Format::Json.serialize(&payload)?;
+        let signature = algorithm::hmac::<D>(&input, secret.as_ref());
+        Ok(Rwt {
+            payload,
+            signature,
+            header: Header::default(),
+            format: Format::Json,
+            marker: PhantomData,
+        })
+    }
+
+    /// Create a web token with the provided payload and encode it in one go.
+    ///
+    /// This returns the same token as `with_payload`, along with the same string `encode` would
+    /// produce for it, but the payload is serialized only once rather than once for each.
+    pub fn with_payload_encoded<S: AsRef<[u8]>>(payload: T, secret: S) -> Result<(Rwt<T>, String)> {
+        let body = Format::Json.serialize(&payload)?;
+        let header = Header::default();
+        let rwt = Rwt::sign_body(payload, &body, header, Format::Json, secret.as_ref())?;
+        let encoded = rwt.encode_body(&body)?;
+        Ok((rwt, encoded))
+    }
+
+    /// Create a web token whose signature also covers some additional data.
+    ///
+    /// The additional data (think request path, client address, or nonce) is *not* part of the
+    /// token. A verifier has to supply the same bytes to `is_valid_aad`, and the token is good for
+    /// that data and nothing else; plain old `is_valid` will always reject it.
+    pub fn with_payload_aad<S, B>(payload: T, secret: S, aad: B) -> Result<Rwt<T>>
+    where
+        S: AsRef<[u8]>,
+        B: AsRef<[u8]>,
+    {
+        let body = Format::Json.serialize(&payload)?;
+        let algorithm = Algorithm::default();
+        let input = with_aad(&body, aad.as_ref());
+        let signature = algorithm.sign(&input, secret.as_ref())?;
+        Ok(Rwt {
+            payload,
+            signature,
+            header: Header::new(algorithm, None),
+            format: Format::Json,
+            marker: PhantomData,
+        })
+    }
+
+    /// Create a web token whose signature is cut down to `tag_len` bytes.
+    ///
+    /// This is for transports where every character counts: a 16-byte tag is 24 characters of
+    /// base64 instead of 44. It's also sixteen bytes of security margin instead of thirty-two, so
+    /// don't do it unless you need to. Anything shorter than `MIN_TAG_LEN`, or longer than the
+    /// MAC itself, is rejected with `Error::Format`. Check the token with `is_valid_truncated`,
+    /// since `is_valid` expects the whole MAC and will always reject it.
+    pub fn with_payload_truncated<S: AsRef<[u8]>>(
+        payload: T,
+        secret: S,
+        tag_len: usize,
+    ) -> Result<Rwt<T>> {
+        let mut rwt = Rwt::with_payload(payload, secret)?;
+        rwt.signature = truncate_tag(&rwt.signature, tag_len)?;
+        Ok(rwt)
+    }
+
+    /// Encode a payload as the body segment of a token, with no signature at all.
+    ///
+    /// This is the body `encode` would write, for tools that show what a token says rather than
+    /// vouch for it. **Nothing here is signed**, and nothing that reads it can tell whether the
+    /// payload is genuine. Read it back with `decode_payload`.
+    pub fn encode_payload_only(payload: &T) -> Result<String> {
+        Ok(base64::encode(Format::Json.serialize(payload)?))
+    }
+
+    /// Sign a payload without making a token of it.
+    ///
+    /// This returns only the signature, in standard base64, for payloads too big to embed that
+    /// travel some other way; think files. Check it with `verify_detached`.
+    pub fn sign_detached<S: AsRef<[u8]>>(payload: &T, secret: S) -> Result<String> {
+        let body = Format::Json.serialize(payload)?;
+        Algorithm::default().sign(&body, secret.as_ref())
+    }
+
+    /// Check a signature created by `sign_detached` against the payload it was made for.
+    ///
+    /// Padding on the signature is optional.
+    #[must_use = "verification result must be checked"]
+    pub fn verify_detached<S: AsRef<[u8]>>(payload: &T, signature: &str, secret: S) -> bool {
+        match base64::decode(signature) {
+            Err(_) => false,
+            Ok(signature) => verify_signature(payload, &signature, secret),
+        }
+    }
+}
+
+impl<T: Signable, A: SigningAlgorithm> Rwt<T, A> {
+    /// Create a web token signed with the algorithm named by its type.
+    ///
+    /// This is `with_payload` for an `Rwt<T, A>` other than the default, as in
+    /// `Rwt::<_, Hs512>::with_payload_typed(payload, secret)`.
+    pub fn with_payload_typed<S: AsRef<[u8]>>(payload: T, secret: S) -> Result<Rwt<T, A>> {
+        let header = Header::new(A::ALGORITHM, None);
+        Rwt::sign(payload, header, Format::Json, secret.as_ref())
+    }
+
+    /// Create a web token, handing the signer a source of randomness in case it wants one.
+    ///
+    /// None of the algorithms here do yet. HMAC is deterministic, so `rng` goes untouched and the
+    /// token is exactly the one `with_payload_typed` would make. A randomized signer (ECDSA, say)
+    /// will draw its nonces from `rng`, so a test can seed it and get the same token every time.
+    /// Code that signs this way now won't need to change when one turns up.
+    #[cfg(feature = "rand")]
+    pub fn sign_with_rng<S, R>(payload: T, secret: S, rng: &mut R) -> Result<Rwt<T, A>>
+    where
+        S: AsRef<[u8]>,
+        R: rand::RngCore + rand::CryptoRng,
+    {
+        let _ = rng;
+        Rwt::with_payload_typed(payload, secret)
+    }
+}
+
+impl<T: Signable, A> Rwt<T, A> {
+    fn sign(payload: T, header: Header, format: Format, secret: &[u8]) -> Result<Rwt<T, A>> {
+        let body = format.serialize(&payload)?;
+        Rwt::sign_body(payload, &body, header, format, secret)
+    }
+
+    /// Sign a payload which has already been serialized as `body`.
+    fn sign_body(
+        payload: T,
+        body: &[u8],
+        header: Header,
+        format: Format,
+        secret: &[u8],
+    ) -> Result<Rwt<T, A>> {
+        let signature = header.alg.sign(&header.signing_input(body)?, secret)?;
         Ok(Rwt {
-            payload: payload,
-            signature: signature,
+            payload,
+            signature,
+            header,
+            format,
+            marker: PhantomData,
         })
     }
 
@@ -57,121 +641,2217 @@ impl<T: Serialize> Rwt<T> {
     /// In this case, "the usual format" means `xxx.xxx` where the left hand side is the token
     /// itself and the right hand side is the signature. The base64 implementation used currently
     /// introduces padding into the equation.
+    ///
+    /// A token carrying a key id gets a third segment up front: `kid.xxx.xxx`.
+    ///
+    /// The length of the result is known before any of it is written, so it's allocated once, at
+    /// exactly the right size.
+    #[must_use = "encoding returns the token and changes nothing else"]
     pub fn encode(&self) -> Result<String> {
-        let body = base64::encode(json::to_string(&self.payload)?.as_bytes());
-        Ok(format!("{}.{}", body, self.signature))
+        self.encode_body(&self.format.serialize(&self.payload)?)
     }
 
-    /// Validate the token.
+    /// `encode`, given the serialized payload.
+    fn encode_body(&self, body: &[u8]) -> Result<String> {
+        let header = self.header.encode(base64::STANDARD)?;
+        let len = header.as_ref().map_or(0, |header| header.len() + 1)
+            + self.format.prefix().len()
+            + padded_base64_len(body.len())
+            + 1
+            + self.signature.len();
+
+        let mut buf = String::with_capacity(len);
+        self.write_segments(
+            &mut buf,
+            header.as_deref(),
+            base64::STANDARD,
+            '.',
+            body,
+            &self.signature,
+        )?;
+        debug_assert_eq!(len, buf.len());
+        Ok(buf)
+    }
+
+    /// Encode the token with a leading version tag: `1.xxx.xxx`.
     ///
-    /// This function compares the token as serialized against a freshly-derived signature to
-    /// ensure that it is original and un-tampered-with. This version uses `rust-crypto` to
-    /// compare the two results in order to protect against timing attacks.
-    pub fn is_valid<S: AsRef<[u8]>>(&self, secret: S) -> bool {
-        match derive_signature(&self.payload, Sha256::new(), secret.as_ref()) {
-            Err(_) => false,
-            Ok(signature) => {
-                crypto::util::fixed_time_eq(self.signature.as_bytes(), signature.as_bytes())
-            }
+    /// The tag is a single character, which can never be mistaken for a base64 segment. Current
+    /// tokens are version `1`. Every parser in this crate accepts tagged and untagged tokens
+    /// alike, and refuses a tag it doesn't know with `Error::Format` rather than guessing. Tag
+    /// your tokens if you expect to have verifiers of different vintages.
+    #[must_use = "encoding returns the token and changes nothing else"]
+    pub fn encode_versioned(&self) -> Result<String> {
+        let mut buf = String::from(VERSION);
+        buf.push('.');
+        self.write_to(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Encode the token into an existing buffer.
+    ///
+    /// The buffer is cleared first. This produces the same thing as `encode`, but a buffer reused
+    /// across many tokens saves an allocation apiece.
+    pub fn encode_into(&self, buf: &mut String) -> Result<()> {
+        buf.clear();
+        self.write_to(buf)
+    }
+
+    /// Write the encoded token to `w`.
+    ///
+    /// This is `encode` for anything implementing `fmt::Write`, with no intermediate `String`.
+    pub fn write_to<W: fmt::Write>(&self, w: &mut W) -> Result<()> {
+        self.write_config(w, base64::STANDARD, '.', &self.signature)
+    }
+
+    /// Encode the token as `encode` would, but with `sep` between the segments instead of `.`.
+    ///
+    /// This is for contexts in which `.` means something. The separator can't be anything that
+    /// might turn up in base64 (or, for tokens that aren't JSON, the body's marker), so letters,
+    /// digits, and `+/-_=` are all rejected with `Error::Format`. Parse the result with
+    /// `Rwt::parse_with_separator`.
+    #[must_use = "encoding returns the token and changes nothing else"]
+    pub fn encode_with_separator(&self, sep: char) -> Result<String> {
+        check_separator(sep)?;
+        if self.format.prefix().contains(sep) {
+            let message = format!("{:?} can't separate a {:?} token", sep, self.format);
+            return Err(Error::Format(message));
         }
+        if sep == header::MARKER && !self.header.is_compact() {
+            let message = format!("{:?} can't separate a token with a full header", sep);
+            return Err(Error::Format(message));
+        }
+
+        let mut buf = String::new();
+        self.write_config(&mut buf, base64::STANDARD, sep, &self.signature)?;
+        Ok(buf)
     }
-}
 
-impl<T, E> FromStr for Rwt<T>
-where
-    E: Display,
-    T: FromStr<Err = E>,
-{
-    type Err = Error;
+    /// Encode the token using the URL-safe base64 alphabet, without padding.
+    ///
+    /// This is the same `xxx.xxx` format as `encode`, but both halves use `-` and `_` in place of
+    /// `+` and `/` and omit the trailing `=`, which makes the result safe to drop into a query
+    /// string. Parse it with `Rwt::parse_url_safe`.
+    #[must_use = "encoding returns the token and changes nothing else"]
+    pub fn encode_url_safe(&self) -> Result<String> {
+        self.encode_config(base64::URL_SAFE_NO_PAD)
+    }
 
-    fn from_str(s: &str) -> Result<Self> {
-        use std::str;
-
-        let mut parts = s.split('.');
-        let payload = parts
-            .next()
-            .ok_or_else(|| Error::Format(format!("Missing body: {:?}", s)))?;
-        let signature = parts
-            .next()
-            .ok_or_else(|| Error::Format(format!("Missing signature: {:?}", s)))?;
-
-        let payload = base64::decode(payload)?;
-        let payload = str::from_utf8(&payload)?;
-        let payload = payload
-            .parse::<T>()
-            .map_err(|e| Error::FromStr(format!("Unable to parse body as payload: {}", e)))?;
+    /// Encode the token without base64 padding.
+    ///
+    /// This is `encode` minus the trailing `=` on each segment, which makes for a shorter token.
+    /// `FromStr` accepts both forms.
+    #[must_use = "encoding returns the token and changes nothing else"]
+    pub fn encode_unpadded(&self) -> Result<String> {
+        self.encode_config(base64::STANDARD_NO_PAD)
+    }
 
-        Ok(Rwt {
-            payload: payload,
-            signature: signature.to_owned(),
+    /// Encode the token as `encode` would, but with some other flavor of base64.
+    ///
+    /// Parse the result with `Rwt::parse_with_codec`, using the same codec.
+    #[must_use = "encoding returns the token and changes nothing else"]
+    pub fn encode_with_codec<C: Base64Codec>(&self, codec: C) -> Result<String> {
+        let body = codec.encode(&self.format.serialize(&self.payload)?);
+        let signature = codec.encode(&base64::decode(&self.signature)?);
+        let prefix = self.format.prefix();
+        Ok(match self.header.encode(&codec)? {
+            None => format!("{}{}.{}", prefix, body, signature),
+            Some(header) => format!("{}.{}{}.{}", header, prefix, body, signature),
         })
     }
-}
 
-fn derive_signature<D, T, S>(payload: &T, digest: D, secret: S) -> Result<String>
-where
-    T: Serialize,
-    D: Digest,
-    S: AsRef<[u8]>,
-{
-    let mut hmac = Hmac::new(digest, secret.as_ref());
-    hmac.input(json::to_string(payload)?.as_bytes());
-    Ok(base64::encode(hmac.result().code()))
-}
+    fn encode_config(&self, config: base64::Config) -> Result<String> {
+        let signature = base64::encode_config(base64::decode(&self.signature)?, config);
+        let mut buf = String::new();
+        self.write_config(&mut buf, config, '.', &signature)?;
+        Ok(buf)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::Rwt;
-    use serde::{Deserialize, Serialize};
-    use serde_json;
-    use std::str::FromStr;
+    fn write_config<W: fmt::Write>(
+        &self,
+        w: &mut W,
+        config: base64::Config,
+        sep: char,
+        signature: &str,
+    ) -> Result<()> {
+        let body = self.format.serialize(&self.payload)?;
+        self.write_parts(w, config, sep, &body, signature)
+    }
 
-    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
-    struct Payload {
-        jti: String,
-        exp: i64,
+    fn write_parts<W: fmt::Write>(
+        &self,
+        w: &mut W,
+        config: base64::Config,
+        sep: char,
+        body: &[u8],
+        signature: &str,
+    ) -> Result<()> {
+        let header = self.header.encode(config)?;
+        self.write_segments(w, header.as_deref(), config, sep, body, signature)
     }
 
-    impl FromStr for Payload {
-        type Err = &'static str;
+    fn write_segments<W: fmt::Write>(
+        &self,
+        w: &mut W,
+        header: Option<&str>,
+        config: base64::Config,
+        sep: char,
+        body: &[u8],
+        signature: &str,
+    ) -> Result<()> {
+        let prefix = self.format.prefix();
+        let body = Base64Display::with_config(body, config);
+        match header {
+            None => write!(w, "{}{}{}{}", prefix, body, sep, signature),
+            Some(header) => write!(w, "{}{}{}{}{}{}", header, sep, prefix, body, sep, signature),
+        }
+        .map_err(|_| Error::Format("Unable to write token".into()))
+    }
 
-        fn from_str(s: &str) -> Result<Self, Self::Err> {
-            serde_json::from_str(s).map_err(|_| "Sorry, Charlie.")
+    /// Verify the token using the algorithm you expect, and no other.
+    ///
+    /// The signature is derived with `expected`, whatever the token says, and a token claiming
+    /// some other algorithm is rejected with `Error::InvalidSignature` before any hashing
+    /// happens. This closes the door on the old JWT trick of picking your own algorithm.
+    #[must_use = "verification result must be checked"]
+    pub fn verify_with_alg<S: AsRef<[u8]>>(&self, secret: S, expected: Algorithm) -> Result<()> {
+        traced(self.verify_untraced(secret.as_ref(), expected))
+    }
+
+    fn verify_untraced(&self, secret: &[u8], expected: Algorithm) -> Result<()> {
+        if self.header.alg != expected {
+            return Err(Error::InvalidSignature);
         }
+        let input = self.signing_input()?;
+        check_signature(expected, &input, &self.signature, secret)
     }
 
-    #[test]
-    fn create_rwt_with_payload() {
-        create_rwt();
+    /// The exact bytes the signature covers.
+    ///
+    /// For a token without a key id, that's just the serialized payload. Handy for working out
+    /// why a signature doesn't match; this is byte for byte what `verify` hashes.
+    pub fn signing_input(&self) -> Result<Vec<u8>> {
+        let body = self.format.serialize(&self.payload)?;
+        Ok(self.header.signing_input(&body)?.into_owned())
     }
 
-    #[test]
-    fn validate_rwt() {
-        let rwt = create_rwt();
-        assert!(rwt.is_valid("secret"));
+    /// Sign the token again with `secret`, replacing the signature and nothing else.
+    ///
+    /// This is for re-signing after a key rotation, where `map_payload` would move the payload
+    /// for no reason. The new signature covers exactly what `verify` would check: the payload as
+    /// it is now, along with the header, using the token's algorithm. On error, the token is left
+    /// as it was. As always, verify the old signature first if the token came from outside.
+    pub fn resign_in_place<S: AsRef<[u8]>>(&mut self, secret: S) -> Result<()> {
+        let input = self.signing_input()?;
+        self.signature = self.header.alg.sign(&input, secret.as_ref())?;
+        Ok(())
     }
 
-    #[test]
-    fn invalidate_rwt() {
-        let rwt = create_rwt();
-        assert!(!rwt.is_valid("other secret"));
+    /// Validate a token created by `with_payload_digest`.
+    ///
+    /// As with `with_payload_digest`, only the type of `digest` matters.
+    #[must_use = "verification result must be checked"]
+    pub fn is_valid_digest<D, S>(&self, secret: S, _digest: D) -> bool
+    where
+        D: Digest + BlockSizeUser,
+        S: AsRef<[u8]>,
+    {
+        match self.signing_input() {
+            Err(_) => false,
+            Ok(input) => {
+                let signature = algorithm::hmac::<D>(&input, secret.as_ref());
+                fixed_time_eq(&self.signature, &signature)
+            }
+        }
     }
+}
 
-    #[test]
-    fn serialize_rwt() {
-        let rwt = create_rwt();
-        assert_eq!(
-            "eyJqdGkiOiJ0aGlzIG9uZSIsImV4cCI6MTN9.\
-                    Ir9W3KCkyGNmsPFURs4Sj7aQSkuvcqpQ7kTk4F6wCyU=",
-            rwt.encode().unwrap()
-        );
+impl<T: Signable, A: SigningAlgorithm> Rwt<T, A> {
+    /// Validate the token.
+    ///
+    /// This is `verify` for those who don't care why a token was rejected. They do have to care
+    /// *whether* it was, though, so ignoring the answer is a warning:
+    ///
+    /// ```compile_fail
+    /// # #![deny(unused_must_use)]
+    /// # use rwt::Rwt;
+    /// let token = Rwt::with_payload("my payload", "secret").unwrap();
+    /// token.is_valid("secret");
+    /// ```
+    #[must_use = "verification result must be checked"]
+    pub fn is_valid<S: AsRef<[u8]>>(&self, secret: S) -> bool {
+        self.verify(secret).is_ok()
     }
 
-    #[test]
-    fn deserialize_rwt() {
-        let rwt = create_rwt().encode().unwrap();
-        let rwt = rwt.parse::<Rwt<Payload>>().unwrap();
-        assert_eq!(rwt, create_rwt());
+    /// Verify the token.
+    ///
+    /// This function compares the token as serialized against a freshly-derived signature to
+    /// ensure that it is original and un-tampered-with. The two results are compared in
+    /// constant time (courtesy of `subtle`) in order to protect against timing attacks.
+    ///
+    /// The signature is derived using the algorithm named by the token's type: HMAC-SHA256 for a
+    /// plain `Rwt<T>`, HMAC-SHA512 for an `Rwt<T, Hs512>`, and so on. Whatever the token's header
+    /// says is never used to pick the algorithm; a header naming any other is rejected with
+    /// `Error::InvalidSignature`, as is a mismatched signature. A payload that can't be
+    /// re-serialized yields `Error::Json`. For algorithms with no type of their own, use
+    /// `verify_with_alg`.
+    #[must_use = "verification result must be checked"]
+    pub fn verify<S: AsRef<[u8]>>(&self, secret: S) -> Result<()> {
+        self.verify_with_alg(secret, A::ALGORITHM)
+    }
+
+    /// Verify the token and, if it checks out, hand over the payload.
+    ///
+    /// This is for when the payload is all you're after. Errors are those of `verify`.
+    pub fn into_payload<S: AsRef<[u8]>>(self, secret: S) -> Result<T> {
+        self.verify(secret)?;
+        Ok(self.payload)
+    }
+
+    /// Verify the token, then hold the payload to rules of your own.
+    ///
+    /// This is the place for checks the crate can't know about, like a tenant that has to match
+    /// the request's. `check` runs only once the signature has checked out, so it never sees a
+    /// forged payload; a bad signature yields `Error::InvalidSignature` without calling it at all.
+    /// Whatever `check` objects to comes back as `Error::ClaimRejected`.
+    #[must_use = "verification result must be checked"]
+    pub fn verify_with<S, F>(&self, secret: S, check: F) -> Result<()>
+    where
+        S: AsRef<[u8]>,
+        F: FnOnce(&T) -> Result<(), String>,
+    {
+        self.verify(secret)?;
+        check(&self.payload).map_err(Error::ClaimRejected)
+    }
+
+    /// Validate a token created by `with_payload_aad`, given the same additional data.
+    #[must_use = "verification result must be checked"]
+    pub fn is_valid_aad<S, B>(&self, secret: S, aad: B) -> bool
+    where
+        S: AsRef<[u8]>,
+        B: AsRef<[u8]>,
+    {
+        match self.signing_input() {
+            Err(_) => false,
+            Ok(_) if self.header.alg != A::ALGORITHM => false,
+            Ok(input) => {
+                let input = with_aad(&input, aad.as_ref());
+                check_signature(A::ALGORITHM, &input, &self.signature, secret.as_ref()).is_ok()
+            }
+        }
+    }
+
+    /// Validate a token created by `with_payload_truncated`, given the same tag length.
+    ///
+    /// The fresh MAC is truncated just as the token's was before the two are compared, and the
+    /// tag length has to be the one you expect: a token can't shorten its way past this check.
+    #[must_use = "verification result must be checked"]
+    pub fn is_valid_truncated<S: AsRef<[u8]>>(&self, secret: S, tag_len: usize) -> bool {
+        if self.header.alg != A::ALGORITHM {
+            return false;
+        }
+        let expected = self
+            .signing_input()
+            .and_then(|input| A::ALGORITHM.sign(&input, secret.as_ref()))
+            .and_then(|signature| truncate_tag(&signature, tag_len));
+
+        match expected {
+            Err(_) => false,
+            Ok(expected) => fixed_time_eq(&self.signature, &expected),
+        }
+    }
+
+    /// Validate the token against any one of several secrets.
+    ///
+    /// This is meant for secret rotation, where tokens signed with either the outgoing or the
+    /// incoming secret should be accepted for a while. Every secret is checked, even after one
+    /// matches, so the time taken doesn't reveal which of them signed the token.
+    #[must_use = "verification result must be checked"]
+    pub fn is_valid_any<S: AsRef<[u8]>>(&self, secrets: &[S]) -> bool {
+        if self.header.alg != A::ALGORITHM {
+            return false;
+        }
+        let input = match self.signing_input() {
+            Err(_) => return false,
+            Ok(input) => input,
+        };
+
+        secrets.iter().fold(false, |valid, secret| {
+            match A::ALGORITHM.sign(&input, secret.as_ref()) {
+                Err(_) => valid,
+                Ok(signature) => fixed_time_eq(&self.signature, &signature) | valid,
+            }
+        })
+    }
+}
+
+impl<'a, T> Rwt<Cow<'a, T>>
+where
+    T: ToOwned + ?Sized,
+    Cow<'a, T>: Signable,
+{
+    /// Create a web token which borrows its payload.
+    ///
+    /// The token signs, encodes, and verifies just as one made by `with_payload`, and its
+    /// signature is the same, but the payload stays where it is instead of being moved (or cloned)
+    /// into the token.
+    pub fn with_payload_ref<S: AsRef<[u8]>>(payload: &'a T, secret: S) -> Result<Self> {
+        Rwt::with_payload(Cow::Borrowed(payload), secret)
+    }
+
+    /// Take ownership of the payload, cloning it if it's borrowed.
+    pub fn into_owned(self) -> Rwt<T::Owned> {
+        Rwt {
+            payload: self.payload.into_owned(),
+            signature: self.signature,
+            header: self.header,
+            format: self.format,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: fmt::Debug, A> fmt::Debug for Rwt<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.debug_fields(f, &format_args!("<redacted>"))
+    }
+}
+
+/// Writes the encoded form of the token.
+///
+/// Formatting fails with `fmt::Error` if the payload can't be serialized. If you need to know
+/// *why*, call `encode` instead.
+impl<T: Signable, A> Display for Rwt<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_to(f).map_err(|_| fmt::Error)
+    }
+}
+
+impl<T, E> Rwt<T>
+where
+    E: fmt::Debug,
+    T: FromStr<Err = E>,
+{
+    /// Parse a token and verify its signature in a single step.
+    ///
+    /// The signature is re-derived from the decoded body and compared (in fixed time) against the
+    /// signature carried by the token. A mismatch yields `Error::InvalidSignature`, and the payload
+    /// is never parsed at all unless the signature checks out. Prefer this to `FromStr` whenever
+    /// the token comes from somewhere you don't control.
+    pub fn parse_verified<S: AsRef<[u8]>>(s: &str, secret: S) -> Result<Rwt<T>> {
+        Rwt::parse_verified_alg(s, secret, Algorithm::default())
+    }
+
+    /// Parse a token (without verifying it) from raw bytes, such as an HTTP header value.
+    ///
+    /// Input that isn't valid UTF-8 is rejected with `Error::Encoding`. Like `FromStr`, **this
+    /// does not check the signature**; call `is_valid` afterward.
+    pub fn parse_bytes(bytes: &[u8]) -> Result<Rwt<T>> {
+        core::str::from_utf8(bytes)?.parse()
+    }
+
+    /// Parse a token (without verifying it), forgiving the mess it arrived in.
+    ///
+    /// Surrounding whitespace (a trailing newline from a config file, say) is trimmed, as is a
+    /// `Bearer ` prefix copied along with the token from an `Authorization` header. Only the
+    /// wrapping goes; the token itself is parsed exactly as `FromStr` would parse it. Like
+    /// `FromStr`, **this does not check the signature**; call `is_valid` afterward.
+    pub fn parse_trimmed(s: &str) -> Result<Rwt<T>> {
+        let s = s.trim();
+        s.strip_prefix("Bearer ").unwrap_or(s).trim_start().parse()
+    }
+
+    /// Parse a token (without verifying it) from the value of an `Authorization` header.
+    ///
+    /// The value has to use the `Bearer` scheme, in any case, as RFC 6750 allows; anything else,
+    /// `Basic` included, is rejected with `Error::Format`. Like `FromStr`, **this does not check
+    /// the signature**; call `is_valid` afterward.
+    pub fn from_bearer(header_value: &str) -> Result<Rwt<T>> {
+        strip_bearer(header_value)?.parse()
+    }
+
+    /// Parse a token (without verifying it), refusing input longer than `max_len` bytes.
+    ///
+    /// The length is checked before anything is decoded or allocated, so an oversized token
+    /// costs nothing to reject. Tokens from untrusted sources should go through this (or
+    /// `parse_verified_with_limit`) with some sane bound, like 8 KiB.
+    pub fn parse_with_limit(s: &str, max_len: usize) -> Result<Rwt<T>> {
+        check_len(s, max_len)?;
+        s.parse()
+    }
+
+    /// Parse and verify a token, refusing input longer than `max_len` bytes.
+    pub fn parse_verified_with_limit<S: AsRef<[u8]>>(
+        s: &str,
+        secret: S,
+        max_len: usize,
+    ) -> Result<Rwt<T>> {
+        check_len(s, max_len)?;
+        Rwt::parse_verified(s, secret)
+    }
+
+    /// Parse a token and verify its signature using the given algorithm.
+    pub fn parse_verified_alg<S: AsRef<[u8]>>(
+        s: &str,
+        secret: S,
+        algorithm: Algorithm,
+    ) -> Result<Rwt<T>> {
+        traced(Rwt::parse_verified_untraced(
+            s,
+            base64::STANDARD,
+            secret.as_ref(),
+            algorithm,
+        ))
+    }
+
+    /// Parse and verify a token in the current format or any encoding this crate has written.
+    ///
+    /// This is for migrations, where tokens issued by an older release (or by a service still
+    /// using `encode_url_safe`) are out there and shouldn't all be invalidated at once. The
+    /// current format is tried first, exactly as `parse_verified` tries it, and only a token that
+    /// fails there on its base64 or its signature goes on to the legacy encodings:
+    ///
+    /// * URL-safe base64 without padding, as written by `encode_url_safe`.
+    ///
+    /// That's the whole list, for now. Tokens from 0.3 and earlier are the current format byte for
+    /// byte, and the current format has accepted signatures with or without padding since
+    /// unpadded encoding turned up. Every attempt compares signatures in constant time. If none
+    /// of them works, the error is `Error::InvalidSignature` if any attempt got as far as the
+    /// signature, and otherwise the one from the current format.
+    pub fn parse_compat<S: AsRef<[u8]>>(s: &str, secret: S) -> Result<Rwt<T>> {
+        let (secret, algorithm) = (secret.as_ref(), Algorithm::default());
+        let current = match Rwt::parse_verified_untraced(s, base64::STANDARD, secret, algorithm) {
+            Err(e @ Error::Base64(_)) | Err(e @ Error::InvalidSignature) => e,
+            result => return traced(result),
+        };
+
+        let legacy = Rwt::parse_verified_untraced(s, base64::URL_SAFE_NO_PAD, secret, algorithm);
+        traced(match legacy {
+            Err(Error::InvalidSignature) => Err(Error::InvalidSignature),
+            Err(_) => Err(current),
+            ok => ok,
+        })
+    }
+
+    fn parse_verified_untraced(
+        s: &str,
+        codec: impl Base64Codec,
+        secret: &[u8],
+        algorithm: Algorithm,
+    ) -> Result<Rwt<T>> {
+        let segments = split_parts(s)?;
+        let (header, body) = segments.decode(&codec, algorithm)?;
+        if header.alg != algorithm {
+            return Err(Error::InvalidSignature);
+        }
+        let signature = segments.signature(&codec)?;
+        let input = header.signing_input(&body)?;
+        check_signature(algorithm, &input, &signature, secret)?;
+
+        Ok(Rwt {
+            payload: parse_payload(&body)?,
+            signature,
+            header,
+            format: segments.format,
+            marker: PhantomData,
+        })
+    }
+
+    /// Parse and verify a batch of tokens signed with the same secret.
+    ///
+    /// There's one result per token, in order, so you can see exactly which ones failed and why.
+    /// Each token is verified on its own, just as `parse_verified` would do it, and a bad one
+    /// doesn't cut the batch short.
+    #[must_use = "verification result must be checked"]
+    pub fn verify_batch<S: AsRef<[u8]>>(tokens: &[&str], secret: S) -> Vec<Result<Rwt<T>>> {
+        let secret = secret.as_ref();
+        tokens
+            .iter()
+            .map(|token| Rwt::parse_verified(token, secret))
+            .collect()
+    }
+
+    /// Parse a token produced by `encode_with_separator`.
+    ///
+    /// Like `FromStr`, **this does not check the signature**; call `is_valid` afterward.
+    pub fn parse_with_separator(s: &str, sep: char) -> Result<Rwt<T>> {
+        check_separator(sep)?;
+        let segments = split_parts_with(s, sep)?;
+        let (header, body) = segments.decode(base64::STANDARD, Algorithm::default())?;
+        Ok(Rwt {
+            payload: parse_payload(&body)?,
+            signature: segments.signature(base64::STANDARD)?,
+            header,
+            format: segments.format,
+            marker: PhantomData,
+        })
+    }
+
+    /// Parse a token produced by `encode_with_codec`.
+    ///
+    /// Like `FromStr`, **this does not check the signature**; call `is_valid` afterward.
+    pub fn parse_with_codec<C: Base64Codec>(s: &str, codec: C) -> Result<Rwt<T>> {
+        let segments = split_parts(s)?;
+        let (header, body) = segments.decode(&codec, Algorithm::default())?;
+        Ok(Rwt {
+            payload: parse_payload(&body)?,
+            signature: segments.signature(&codec)?,
+            header,
+            format: segments.format,
+            marker: PhantomData,
+        })
+    }
+
+    /// Parse a token produced by `encode_url_safe`.
+    ///
+    /// Like `FromStr`, **this does not check the signature**; call `is_valid` afterward.
+    pub fn parse_url_safe(s: &str) -> Result<Rwt<T>> {
+        let segments = split_parts(s)?;
+        let (header, body) = segments.decode(base64::URL_SAFE_NO_PAD, Algorithm::default())?;
+        Ok(Rwt {
+            payload: parse_payload(&body)?,
+            signature: segments.signature(base64::URL_SAFE_NO_PAD)?,
+            header,
+            format: segments.format,
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: Deserialize<'a>> Rwt<T> {
+    /// Parse a token whose payload borrows from its decoded body.
+    ///
+    /// The body is decoded into `buf`, which the caller owns, and the payload is deserialized
+    /// straight out of it with serde. This means `T` can hold `&str` and `&[u8]` fields that
+    /// point into `buf`, and a service can reuse one buffer for token after token instead of
+    /// allocating each time. (Keep in mind that serde can only borrow a string that contains no
+    /// escape sequences.) Any previous contents of `buf` are discarded.
+    ///
+    /// Like `FromStr`, **this does not check the signature**; call `is_valid` afterward.
+    pub fn parse_borrowed(s: &str, buf: &'a mut Vec<u8>) -> Result<Rwt<T>> {
+        let segments = split_parts(s)?;
+        segments.expect_json()?;
+        let header = segments.decode_header(base64::STANDARD, Algorithm::default())?;
+
+        buf.clear();
+        base64::decode_config_buf(segments.body, base64::STANDARD, buf)?;
+        let buf: &'a [u8] = buf;
+
+        Ok(Rwt {
+            payload: json::from_slice(buf)?,
+            signature: segments.signature(base64::STANDARD)?,
+            header,
+            format: segments.format,
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: DeserializeOwned> Rwt<T> {
+    /// Parse a token, deserializing the payload with serde instead of `FromStr`.
+    ///
+    /// This is `FromStr` for payloads that derive `Deserialize` and nothing else, which spares
+    /// you the `impl FromStr` that would only have called `serde_json::from_str` anyway. It's also
+    /// where the `simd-json` feature, if you've turned it on, does its parsing. Like
+    /// `FromStr`, **this does not check the signature**; call `is_valid` afterward.
+    pub fn from_json_str(s: &str) -> Result<Rwt<T>> {
+        let segments = split_parts(s)?;
+        let (header, body) = segments.decode(base64::STANDARD, Algorithm::default())?;
+        Ok(Rwt {
+            payload: format::from_json(body)?,
+            signature: segments.signature(base64::STANDARD)?,
+            header,
+            format: segments.format,
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Rwt<json::Value> {
+    /// Parse a token whose payload has no type of its own.
+    ///
+    /// For gateways and the like, which pass claims along without knowing their shape. A token
+    /// signed with a `serde_json::Value` payload parses back into an equal value which validates
+    /// against the same secret. Like `FromStr`, **this does not check the signature**; call
+    /// `is_valid` afterward.
+    ///
+    /// That guarantee is for tokens signed with a `Value`, whose object keys come out sorted.
+    /// `is_valid` checks a re-serialized payload, so a token signed by a struct with its fields in
+    /// some other order won't validate this way. Check those against the bytes they arrived with,
+    /// using `Rwt::<serde_json::Value>::parse_verified`.
+    pub fn parse_value(s: &str) -> Result<Rwt<json::Value>> {
+        Rwt::from_json_str(s)
+    }
+}
+
+/// Parse a token without verifying it.
+///
+/// **This does not check the signature.** The resulting token carries whatever signature was
+/// present in the input, so an attacker-supplied string will happily parse into a token with a
+/// garbage signature. This is fine for "parse now, verify later" workflows, but you must call
+/// `is_valid` before trusting the payload. If you have the secret on hand, use
+/// `Rwt::parse_verified` instead.
+///
+/// Padding is optional. A token with a full header (`$header.body.signature`) records its
+/// algorithm, and the parsed token's header says whatever the token said. Any other token doesn't
+/// record it, so the parsed token assumes the one named by its type: `Algorithm::HmacSha256`,
+/// unless you asked for an `Rwt<T, Hs512>` or the like. Either way, `is_valid` checks with the
+/// type's algorithm, so a token claiming another one parses but never validates.
+///
+/// The payload's own `FromStr` error need only be `Debug`. If it fails, its `Debug` output ends
+/// up in `Error::FromStr`.
+///
+/// Parsing never panics. Whatever the input, the worst it can do is produce an error; the same
+/// goes for every other parser in the crate, and `fuzz/` is there to keep it that way.
+impl<T, E, A> FromStr for Rwt<T, A>
+where
+    A: SigningAlgorithm,
+    E: fmt::Debug,
+    T: FromStr<Err = E>,
+{
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let segments = split_parts(s)?;
+        let (header, body) = segments.decode(base64::STANDARD, A::ALGORITHM)?;
+        Ok(Rwt {
+            payload: parse_payload(&body)?,
+            signature: segments.signature(base64::STANDARD)?,
+            header,
+            format: segments.format,
+            marker: PhantomData,
+        })
+    }
+}
+
+/// Parse a token without verifying it, exactly as `FromStr` does.
+impl<T, E, A> TryFrom<&str> for Rwt<T, A>
+where
+    A: SigningAlgorithm,
+    E: fmt::Debug,
+    T: FromStr<Err = E>,
+{
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// Parse a token without verifying it, exactly as `FromStr` does.
+impl<T, E, A> TryFrom<String> for Rwt<T, A>
+where
+    A: SigningAlgorithm,
+    E: fmt::Debug,
+    T: FromStr<Err = E>,
+{
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+fn check_len(s: &str, max_len: usize) -> Result<()> {
+    if s.len() > max_len {
+        return Err(Error::Format(format!(
+            "Token is {} bytes long; the limit is {}",
+            s.len(),
+            max_len
+        )));
+    }
+    Ok(())
+}
+
+/// The segments of an encoded token, still in base64.
+struct Segments<'a> {
+    header: Option<&'a str>,
+    format: Format,
+    body: &'a str,
+    signature: &'a str,
+}
+
+impl Segments<'_> {
+    /// Decode the header and the body, which must be JSON.
+    ///
+    /// Unless the token has a full header, it gets `alg`.
+    fn decode(&self, codec: impl Base64Codec, alg: Algorithm) -> Result<(Header, Vec<u8>)> {
+        self.expect_json()?;
+        let header = self.decode_header(&codec, alg)?;
+        Ok((header, codec.decode(self.body)?))
+    }
+
+    /// Decode the signature and re-encode it in standard, padded base64.
+    ///
+    /// The signature is held in that form regardless of how it arrived, so that it compares equal
+    /// to a freshly-derived one.
+    fn signature(&self, codec: impl Base64Codec) -> Result<String> {
+        Ok(base64::encode(codec.decode(self.signature)?))
+    }
+
+    fn expect_json(&self) -> Result<()> {
+        if self.format.is_json() {
+            Ok(())
+        } else {
+            let message = format!("Payload is {:?}, not JSON", self.format);
+            Err(Error::Format(message))
+        }
+    }
+
+    fn decode_header(&self, codec: impl Base64Codec, alg: Algorithm) -> Result<Header> {
+        Header::decode(self.header, codec, alg)
+    }
+}
+
+/// Split a token into its segments.
+///
+/// A token is either `body.signature` or, when it carries a key id or a full header,
+/// `header.body.signature`.
+fn split_parts(s: &str) -> Result<Segments<'_>> {
+    split_parts_with(s, '.')
+}
+
+fn split_parts_with(s: &str, sep: char) -> Result<Segments<'_>> {
+    let mut parts = strip_version(s, sep)?.split(sep);
+    let first = parts
+        .next()
+        .ok_or_else(|| Error::Format(with_input("Missing body", s)))?;
+    let second = parts
+        .next()
+        .ok_or_else(|| Error::Format(with_input("Missing signature", s)))?;
+
+    let (header, body, signature) = match parts.next() {
+        None => (None, first, second),
+        Some(signature) => (Some(first), second, signature),
+    };
+    if parts.next().is_some() {
+        return Err(Error::Format(with_input("Too many segments", s)));
+    }
+    // Only a full header can say a token is unsigned, so only a token with one may go without a
+    // signature.
+    let unsigned = header.is_some_and(|header| header.starts_with(header::MARKER));
+    if (signature.is_empty() && !unsigned) || header == Some("") {
+        return Err(Error::Format(with_input("Empty segment", s)));
+    }
+    let (format, body) = Format::detect(body);
+
+    Ok(Segments {
+        header,
+        format,
+        body,
+        signature,
+    })
+}
+
+/// The wire version of the tokens this crate produces. See `Rwt::encode_versioned`.
+const VERSION: &str = "1";
+
+/// Strip the version tag, if there is one, from the front of a token.
+///
+/// A tag is a single character, and no base64 segment can be that short.
+fn strip_version(s: &str, sep: char) -> Result<&str> {
+    match s.split_once(sep) {
+        Some((tag, rest)) if tag.chars().count() == 1 => {
+            if tag == VERSION {
+                Ok(rest)
+            } else {
+                Err(Error::Format(with_input("Unsupported token version", tag)))
+            }
+        }
+        _ => Ok(s),
+    }
+}
+
+/// Strip the `Bearer` scheme from an `Authorization` header value, leaving the token.
+fn strip_bearer(value: &str) -> Result<&str> {
+    let (scheme, token) = value
+        .split_once(' ')
+        .ok_or_else(|| Error::Format("Authorization is missing a scheme".into()))?;
+    if !scheme.eq_ignore_ascii_case("Bearer") {
+        let message = with_input("Authorization scheme is not Bearer", scheme);
+        return Err(Error::Format(message));
+    }
+    Ok(token.trim_start_matches(' '))
+}
+
+/// Make sure `sep` can't be confused with base64.
+fn check_separator(sep: char) -> Result<()> {
+    if sep.is_ascii_alphanumeric() || "+/-_=".contains(sep) {
+        return Err(Error::Format(format!(
+            "{:?} can't separate base64 segments",
+            sep
+        )));
+    }
+    Ok(())
+}
+
+fn parse_payload<T, E>(body: &[u8]) -> Result<T>
+where
+    E: fmt::Debug,
+    T: FromStr<Err = E>,
+{
+    core::str::from_utf8(body)?
+        .parse::<T>()
+        .map_err(|e| Error::FromStr(with_input("Unable to parse body as payload", e)))
+}
+
+/// The length of `len` bytes in padded base64: four characters for every three bytes, or part
+/// thereof.
+fn padded_base64_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+fn fixed_time_eq(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).into()
+}
+
+/// Compare `signature` (in standard base64) against one freshly derived from `input`.
+fn check_signature(
+    algorithm: Algorithm,
+    input: &[u8],
+    signature: &str,
+    secret: &[u8],
+) -> Result<()> {
+    // An unsigned token has no signature to check, and that's not the same as a good one.
+    if algorithm == Algorithm::None {
+        return Err(Error::InvalidSignature);
+    }
+    if fixed_time_eq(signature, &algorithm.sign(input, secret)?) {
+        Ok(())
+    } else {
+        Err(Error::InvalidSignature)
+    }
+}
+
+/// Report a failed verification to `tracing`, if it's listening.
+///
+/// Only the kind of failure goes out; never the secret, the signature, or the token. A bad
+/// signature is a warning, while anything else is just malformed input and only worth a debug
+/// event. Without the `tracing` feature, this is nothing at all.
+#[inline]
+fn traced<T>(result: Result<T>) -> Result<T> {
+    #[cfg(feature = "tracing")]
+    {
+        if let Err(e) = &result {
+            match e {
+                Error::InvalidSignature => {
+                    tracing::warn!(reason = e.category(), "token failed verification")
+                }
+                _ => tracing::debug!(reason = e.category(), "token failed verification"),
+            }
+        }
+    }
+    result
+}
+
+/// Cut a signature (in standard base64) down to its first `tag_len` bytes.
+fn truncate_tag(signature: &str, tag_len: usize) -> Result<String> {
+    let mut tag = base64::decode(signature)?;
+    if tag_len < MIN_TAG_LEN || tag_len > tag.len() {
+        return Err(Error::Format(format!(
+            "Tag length must be between {} and {} bytes, not {}",
+            MIN_TAG_LEN,
+            tag.len(),
+            tag_len
+        )));
+    }
+    tag.truncate(tag_len);
+    Ok(base64::encode(tag))
+}
+
+/// Assemble the bytes covered by the signature.
+///
+/// A token without a key id signs its payload alone, exactly as it always has. Otherwise, the key
+/// id is length-prefixed and placed ahead of the payload so that no key id can bleed into the
+/// payload (or vice versa) and still produce the same signature.
+fn signing_input<'a>(key_id: Option<&str>, payload: &'a [u8]) -> Cow<'a, [u8]> {
+    match key_id {
+        None => Cow::Borrowed(payload),
+        Some(key_id) => {
+            let mut input = Vec::with_capacity(8 + key_id.len() + payload.len());
+            input.extend_from_slice(&(key_id.len() as u64).to_be_bytes());
+            input.extend_from_slice(key_id.as_bytes());
+            input.extend_from_slice(payload);
+            Cow::Owned(input)
+        }
+    }
+}
+
+/// Append additional data to a signing input.
+///
+/// The data is followed by its length, so reading backward from the end there is only one way to
+/// split the result into input and data. Moving bytes from one to the other changes the length,
+/// and with it the signature.
+fn with_aad(input: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(input.len() + aad.len() + 8);
+    buf.extend_from_slice(input);
+    buf.extend_from_slice(aad);
+    buf.extend_from_slice(&(aad.len() as u64).to_be_bytes());
+    buf
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::{
+        decode_body, decode_payload, decode_signature, parts, verify_and_decode, verify_signature,
+        verify_str, Rwt, MIN_TAG_LEN,
+    };
+    use crate::{Algorithm, Error, Header, Hs256, Hs384, Hs512, SigningAlgorithm};
+    use serde::{Deserialize, Serialize};
+    use std::convert::TryInto;
+    use std::str::FromStr;
+
+    #[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+    struct Payload {
+        jti: String,
+        exp: i64,
+    }
+
+    impl FromStr for Payload {
+        type Err = &'static str;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s).map_err(|_| "Sorry, Charlie.")
+        }
+    }
+
+    #[test]
+    fn create_rwt_with_payload() {
+        create_rwt();
+    }
+
+    #[test]
+    fn validate_rwt() {
+        let rwt = create_rwt();
+        assert!(rwt.is_valid("secret"));
+    }
+
+    #[test]
+    fn malformed_input_does_not_panic() {
+        let inputs = [
+            "",
+            ".",
+            "..",
+            "...",
+            "1.",
+            "1..",
+            "é.é",
+            "é",
+            "1é.x.y",
+            "\u{1F600}.\u{1F600}.\u{1F600}",
+            ".x",
+            "x.",
+            "!.~",
+            "====.====",
+            "a.b.c.d.e",
+            "\0.\0",
+        ];
+
+        for input in &inputs {
+            let _ = input.parse::<Rwt<Payload>>();
+            let _ = Rwt::<Payload>::parse_verified(input, "secret");
+            let _ = Rwt::<Payload>::parse_url_safe(input);
+            let _ = Rwt::<Payload>::parse_jwt(input, "secret");
+            let _ = decode_body(input);
+            let _ = decode_signature(input);
+            let _ = verify_str(input, "secret");
+        }
+    }
+
+    #[test]
+    fn parse_bytes() {
+        let token = create_rwt().encode().unwrap();
+        let rwt = Rwt::<Payload>::parse_bytes(token.as_bytes()).unwrap();
+        assert_eq!(create_rwt(), rwt);
+    }
+
+    #[test]
+    fn parse_bytes_rejects_invalid_utf8() {
+        let mut bytes = create_rwt().encode().unwrap().into_bytes();
+        bytes[3] = 0xff;
+        match Rwt::<Payload>::parse_bytes(&bytes) {
+            Err(Error::Encoding(_)) => (),
+            other => panic!("expected Encoding error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trip_with_separator() {
+        let rwt = create_rwt();
+        let encoded = rwt.encode_with_separator('~').unwrap();
+        assert_eq!(rwt.encode().unwrap().replace('.', "~"), encoded);
+        assert_eq!(rwt, Rwt::parse_with_separator(&encoded, '~').unwrap());
+
+        let rwt = Rwt::with_payload_kid(rwt.payload, "secret", "k1").unwrap();
+        let encoded = rwt.encode_with_separator('~').unwrap();
+        let parsed = Rwt::<Payload>::parse_with_separator(&encoded, '~').unwrap();
+        assert_eq!(rwt, parsed);
+        assert!(parsed.is_valid("secret"));
+    }
+
+    #[test]
+    fn reject_extra_segments() {
+        let encoded = create_rwt().encode().unwrap();
+        let kid = Rwt::with_payload_kid(create_rwt().payload, "secret", "k1").unwrap();
+        let tokens = [
+            format!("{}.", encoded),
+            format!("{}.garbage", kid.encode().unwrap()),
+            format!(".{}", encoded),
+        ];
+        for token in &tokens {
+            match Rwt::<Payload>::from_str(token) {
+                Err(Error::Format(_)) => (),
+                other => panic!("expected Format error for {:?}, got {:?}", token, other),
+            }
+        }
+    }
+
+    #[test]
+    fn reject_base64_separator() {
+        for &sep in &['/', 'A', '=', '-'] {
+            match create_rwt().encode_with_separator(sep) {
+                Err(Error::Format(_)) => (),
+                other => panic!("expected Format error, got {:?}", other),
+            }
+        }
+        match Rwt::<Payload>::parse_with_separator("anything", '/') {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn detached_signature() {
+        let payload = create_rwt().payload;
+        let signature = Rwt::sign_detached(&payload, "secret").unwrap();
+        assert_eq!(create_rwt().signature(), signature);
+        assert!(Rwt::verify_detached(&payload, &signature, "secret"));
+        assert!(Rwt::verify_detached(
+            &payload,
+            signature.trim_end_matches('='),
+            "secret"
+        ));
+        assert!(!Rwt::verify_detached(&payload, &signature, "other secret"));
+
+        let modified = Payload {
+            exp: 14,
+            ..payload.clone()
+        };
+        assert!(!Rwt::verify_detached(&modified, &signature, "secret"));
+        assert!(!Rwt::verify_detached(&payload, "not base64!", "secret"));
+    }
+
+    #[test]
+    fn signing_input_is_payload_json() {
+        let rwt = create_rwt();
+        let json = serde_json::to_string(&rwt.payload).unwrap().into_bytes();
+        assert_eq!(json, rwt.signing_input().unwrap());
+
+        let rwt = Rwt::with_payload_kid(rwt.payload, "secret", "k1").unwrap();
+        let input = rwt.signing_input().unwrap();
+        assert_eq!(&[0, 0, 0, 0, 0, 0, 0, 2], &input[..8]);
+        assert_eq!(b"k1", &input[8..10]);
+        assert_eq!(json, &input[10..]);
+    }
+
+    #[test]
+    fn ct_eq_agrees_with_eq() {
+        let rwt = create_rwt();
+        assert!(rwt.ct_eq(&create_rwt()));
+        assert_eq!(rwt, create_rwt());
+
+        let other = Rwt::with_payload(rwt.payload.clone(), "other secret").unwrap();
+        assert!(!rwt.ct_eq(&other));
+        assert_ne!(rwt, other);
+    }
+
+    #[test]
+    fn verify_encoded_str() {
+        let token = create_rwt().encode().unwrap();
+        assert!(verify_str(&token, "secret").is_ok());
+        assert!(verify_str(&create_rwt().encode_unpadded().unwrap(), "secret").is_ok());
+        match verify_str(&token, "other secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+
+        let rwt = Rwt::with_payload_kid(create_rwt().payload, "secret", "k1").unwrap();
+        assert!(verify_str(&rwt.encode().unwrap(), "secret").is_ok());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn sign_with_seeded_rng() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(13);
+        let untouched = StdRng::seed_from_u64(13).gen::<u64>();
+        let rwt = Rwt::<_, Hs256>::sign_with_rng(create_rwt().payload, "secret", &mut rng).unwrap();
+        assert_eq!(create_rwt(), rwt);
+        assert_eq!(untouched, rng.gen::<u64>());
+
+        // Reproducible, whatever the seed.
+        let mut other = StdRng::seed_from_u64(42);
+        let again = Rwt::<_, Hs512>::sign_with_rng(create_rwt().payload, "secret", &mut other);
+        let typed = Rwt::<_, Hs512>::with_payload_typed(create_rwt().payload, "secret");
+        assert_eq!(typed.unwrap(), again.unwrap());
+    }
+
+    #[test]
+    fn parse_compat_accepts_legacy_encodings() {
+        // A token whose URL-safe signature the standard alphabet can't read.
+        let rwt = (0..)
+            .map(|exp| {
+                let payload = Payload {
+                    jti: "this one".into(),
+                    exp,
+                };
+                Rwt::with_payload(payload, "secret").unwrap()
+            })
+            .find(|rwt| rwt.signature().contains(&['+', '/'][..]))
+            .unwrap();
+
+        let current = rwt.encode().unwrap();
+        let legacy = rwt.encode_url_safe().unwrap();
+        assert!(Rwt::<Payload>::parse_verified(&legacy, "secret").is_err());
+
+        for encoded in &[&current, &rwt.encode_unpadded().unwrap(), &legacy] {
+            assert_eq!(rwt, Rwt::parse_compat(encoded, "secret").unwrap());
+            match Rwt::<Payload>::parse_compat(encoded, "other secret") {
+                Err(Error::InvalidSignature) => (),
+                other => panic!("expected InvalidSignature, got {:?}", other),
+            }
+        }
+        match Rwt::<Payload>::parse_compat("garbage", "secret") {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn payload_only_round_trip() {
+        let payload = create_rwt().payload;
+        let body = Rwt::encode_payload_only(&payload).unwrap();
+        assert!(!body.contains('.'));
+        assert_eq!(payload, decode_payload(&body).unwrap());
+
+        let encoded = create_rwt().encode().unwrap();
+        assert_eq!(parts(&encoded).unwrap().0, body);
+
+        match decode_payload::<Payload, _>("!!!!") {
+            Err(Error::Base64(_)) => (),
+            other => panic!("expected Base64 error, got {:?}", other),
+        }
+        match decode_payload::<Payload, _>(&base64::encode([0xff, 0xfe])) {
+            Err(Error::Encoding(_)) => (),
+            other => panic!("expected Encoding error, got {:?}", other),
+        }
+        match decode_payload::<Payload, _>(&base64::encode("{}")) {
+            Err(Error::FromStr(_)) => (),
+            other => panic!("expected FromStr error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unsigned_token_is_never_valid() {
+        let rwt = Rwt::new_unsigned(create_rwt().payload);
+        assert_eq!("this one", rwt.payload.jti);
+        assert_eq!("", rwt.signature());
+
+        for secret in &["secret", "other secret", ""] {
+            assert!(!rwt.is_valid(secret));
+            assert!(!rwt.is_valid_any(&[secret]));
+            match rwt.verify(secret) {
+                Err(Error::InvalidSignature) => (),
+                other => panic!("expected InvalidSignature, got {:?}", other),
+            }
+        }
+        match Rwt::<Payload>::parse_verified(&rwt.encode().unwrap(), "secret") {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn alg_none_round_trip() {
+        let rwt = Rwt::with_payload_unsigned(create_rwt().payload);
+        let encoded = rwt.encode().unwrap();
+        assert!(encoded.starts_with('$') && encoded.ends_with('.'));
+
+        let parsed: Rwt<Payload> = encoded.parse().unwrap();
+        assert_eq!(Algorithm::None, parsed.header().alg);
+        assert_eq!(rwt, parsed);
+        parsed.verify_unsigned().unwrap();
+
+        // A placeholder isn't an unsigned token, and neither is a signed one.
+        for rwt in &[Rwt::new_unsigned(create_rwt().payload), create_rwt()] {
+            match rwt.verify_unsigned() {
+                Err(Error::InvalidSignature) => (),
+                other => panic!("expected InvalidSignature, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn alg_none_is_rejected_by_verify() {
+        let rwt = Rwt::with_payload_unsigned(create_rwt().payload);
+        let encoded = rwt.encode().unwrap();
+
+        for secret in &["secret", ""] {
+            assert!(!rwt.is_valid(secret));
+            assert!(!rwt.is_valid_any(&[secret]));
+            assert!(!rwt.is_valid_truncated(secret, MIN_TAG_LEN));
+            for result in &[
+                rwt.verify(secret),
+                rwt.verify_with_alg(secret, Algorithm::None),
+                verify_str(&encoded, secret),
+                Rwt::<Payload>::parse_verified(&encoded, secret).map(drop),
+            ] {
+                match result {
+                    Err(Error::InvalidSignature) => (),
+                    other => panic!("expected InvalidSignature, got {:?}", other),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn signed_token_cannot_switch_to_alg_none() {
+        let signed = Rwt::with_payload_kid(create_rwt().payload, "secret", "k1")
+            .unwrap()
+            .encode()
+            .unwrap();
+        let (_, rest) = signed.split_once('.').unwrap();
+        let (body, signature) = rest.split_once('.').unwrap();
+        let header = format!("${}", base64::encode(r#"{"alg":"None"}"#));
+
+        // Stripped of its signature, or keeping it, the token is no good to verify.
+        for forged in &[
+            format!("{}.{}.", header, body),
+            format!("{}.{}.{}", header, body, signature),
+        ] {
+            match Rwt::<Payload>::parse_verified(forged, "secret") {
+                Err(Error::InvalidSignature) => (),
+                other => panic!("expected InvalidSignature, got {:?}", other),
+            }
+        }
+
+        // Nor will anything sign with it.
+        let payload = create_rwt().payload;
+        match Rwt::with_payload_alg(payload, "secret", Algorithm::None) {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+        let mut rwt = Rwt::with_payload_unsigned(create_rwt().payload);
+        assert!(rwt.resign_in_place("secret").is_err());
+    }
+
+    #[test]
+    fn verify_and_decode_payload() {
+        let token = create_rwt().encode().unwrap();
+        let payload: Payload = verify_and_decode(&token, "secret").unwrap();
+        assert_eq!(create_rwt().payload, payload);
+
+        match verify_and_decode::<Payload, _, _>(&token, "other secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+        for malformed in &["", "garbage", "!!!!.signature", "a.b.c.d"] {
+            match verify_and_decode::<Payload, _, _>(malformed, "secret") {
+                Err(Error::Format(_)) | Err(Error::Base64(_)) => (),
+                other => panic!("expected Format or Base64 error, got {:?}", other),
+            }
+        }
+
+        let unparseable = Rwt::with_payload("not a payload", "secret").unwrap();
+        match verify_and_decode::<Payload, _, _>(&unparseable.encode().unwrap(), "secret") {
+            Err(Error::FromStr(_)) => (),
+            other => panic!("expected FromStr error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn with_payload_ref() {
+        let payload = create_rwt().payload;
+        let rwt = Rwt::with_payload_ref(&payload, "secret").unwrap();
+        assert!(rwt.is_valid("secret"));
+        assert_eq!(create_rwt().encode().unwrap(), rwt.encode().unwrap());
+
+        assert_eq!(create_rwt(), rwt.into_owned());
+        assert_eq!(13, payload.exp);
+    }
+
+    #[test]
+    fn into_payload() {
+        assert_eq!(13, create_rwt().into_payload("secret").unwrap().exp);
+        assert_eq!(13, create_rwt().payload_ref().exp);
+        match create_rwt().into_payload("other secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn verification_failures_are_traced() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Level, Metadata, Subscriber};
+
+        /// Every event, by level and reason.
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<(Level, String)>>>);
+
+        struct Reason<'a>(&'a mut String);
+
+        impl Visit for Reason<'_> {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                if field.name() == "reason" {
+                    self.0.push_str(value);
+                }
+            }
+
+            fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &Attributes) -> Id {
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event) {
+                let mut reason = String::new();
+                event.record(&mut Reason(&mut reason));
+                let level = *event.metadata().level();
+                self.0.lock().unwrap().push((level, reason));
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let mut tampered = create_rwt();
+        tampered.payload.exp = 14;
+
+        let capture = Capture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            assert!(create_rwt().is_valid("secret"));
+            assert!(!tampered.is_valid("secret"));
+            assert!(Rwt::<Payload>::parse_verified("garbage", "secret").is_err());
+        });
+
+        let events = capture.0.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                (Level::WARN, "signature".to_owned()),
+                (Level::DEBUG, "format".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_trimmed_forgives_wrapping() {
+        let rwt = create_rwt();
+        let encoded = rwt.encode().unwrap();
+        let inputs = [
+            format!("  \t{}", encoded),
+            format!("{}\n", encoded),
+            format!("Bearer {}\r\n", encoded),
+        ];
+        for input in &inputs {
+            let parsed = Rwt::<Payload>::parse_trimmed(input).unwrap();
+            assert_eq!(rwt, parsed);
+            assert!(parsed.is_valid("secret"));
+        }
+        assert!(Rwt::<Payload>::from_str(&inputs[1]).is_err());
+    }
+
+    #[test]
+    fn from_bearer_strips_scheme() {
+        let rwt = create_rwt();
+        let encoded = rwt.encode().unwrap();
+        for scheme in &["Bearer ", "bearer ", "BEARER ", "Bearer   "] {
+            let value = format!("{}{}", scheme, encoded);
+            assert_eq!(rwt, Rwt::<Payload>::from_bearer(&value).unwrap());
+        }
+
+        let values = [format!("Basic {}", encoded), encoded];
+        for value in &values {
+            match Rwt::<Payload>::from_bearer(value) {
+                Err(Error::Format(_)) => (),
+                other => panic!("expected Format error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_tag_round_trip() {
+        let rwt = Rwt::with_payload_truncated(create_rwt().payload, "secret", 16).unwrap();
+        assert_eq!(16, rwt.signature_bytes().unwrap().len());
+        assert!(create_rwt().signature().starts_with(&rwt.signature()[..20]));
+
+        let parsed = Rwt::<Payload>::parse_trimmed(&rwt.encode().unwrap()).unwrap();
+        assert!(parsed.is_valid_truncated("secret", 16));
+        assert!(!parsed.is_valid_truncated("other secret", 16));
+        assert!(!parsed.is_valid_truncated("secret", 12));
+        assert!(!parsed.is_valid("secret"));
+    }
+
+    #[test]
+    fn reject_short_tag() {
+        for &tag_len in &[4, MIN_TAG_LEN - 1, 33] {
+            match Rwt::with_payload_truncated(create_rwt().payload, "secret", tag_len) {
+                Err(Error::Format(_)) => (),
+                other => panic!("expected Format error, got {:?}", other),
+            }
+        }
+        assert!(!create_rwt().is_valid_truncated("secret", 4));
+    }
+
+    #[test]
+    fn resign_in_place_swaps_secrets() {
+        let mut rwt = create_rwt();
+        rwt.resign_in_place("new secret").unwrap();
+        assert!(!rwt.is_valid("secret"));
+        assert!(rwt.is_valid("new secret"));
+        assert_eq!(
+            Rwt::with_payload(create_rwt().payload, "new secret").unwrap(),
+            rwt
+        );
+    }
+
+    #[test]
+    fn verify_with_runs_check_on_authentic_payload() {
+        let rwt = create_rwt();
+        assert!(rwt
+            .verify_with("secret", |payload| match payload.jti.as_str() {
+                "this one" => Ok(()),
+                other => Err(format!("unexpected jti: {}", other)),
+            })
+            .is_ok());
+
+        match rwt.verify_with("secret", |_| Err("wrong tenant".to_owned())) {
+            Err(Error::ClaimRejected(reason)) => assert_eq!("wrong tenant", reason),
+            other => panic!("expected ClaimRejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_with_skips_check_on_bad_signature() {
+        let result = create_rwt().verify_with("other secret", |_| -> Result<(), String> {
+            panic!("check ran on an unverified payload")
+        });
+        match result {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_versioned() {
+        let rwt = create_rwt();
+        let encoded = rwt.encode_versioned().unwrap();
+        assert_eq!(format!("1.{}", rwt.encode().unwrap()), encoded);
+        assert_eq!(rwt, encoded.parse().unwrap());
+        assert!(Rwt::<Payload>::parse_verified(&encoded, "secret").is_ok());
+
+        let rwt = Rwt::with_payload_kid(rwt.payload, "secret", "k1").unwrap();
+        let encoded = rwt.encode_versioned().unwrap();
+        assert_eq!(rwt, Rwt::parse_verified(&encoded, "secret").unwrap());
+    }
+
+    #[test]
+    fn parse_legacy_unversioned() {
+        let rwt = create_rwt();
+        assert_eq!(rwt, rwt.encode().unwrap().parse().unwrap());
+    }
+
+    #[test]
+    fn reject_unknown_version() {
+        let encoded = format!("2.{}", create_rwt().encode().unwrap());
+        match encoded.parse::<Rwt<Payload>>() {
+            Err(Error::Format(message)) => assert!(message.contains("version")),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn clones_hash_alike() {
+        use std::collections::HashSet;
+
+        let rwt = create_rwt();
+        let set: HashSet<_> = vec![rwt.clone(), rwt].into_iter().collect();
+        assert_eq!(1, set.len());
+    }
+
+    #[test]
+    fn encode_into_matches_encode() {
+        let rwt = create_rwt();
+        let mut buf = String::from("leftovers");
+        rwt.encode_into(&mut buf).unwrap();
+        assert_eq!(rwt.encode().unwrap(), buf);
+
+        let rwt = Rwt::with_payload_kid(rwt.payload, "secret", "k1").unwrap();
+        rwt.encode_into(&mut buf).unwrap();
+        assert_eq!(rwt.encode().unwrap(), buf);
+    }
+
+    #[test]
+    fn encode_is_sized_exactly() {
+        let full = Header {
+            typ: Some("rwt".to_owned()),
+            ..Header::default()
+        };
+        let tokens = [
+            create_rwt(),
+            Rwt::with_payload_kid(create_rwt().payload, "secret", "k1").unwrap(),
+            Rwt::with_header(create_rwt().payload, full, "secret").unwrap(),
+        ];
+        for rwt in &tokens {
+            let mut written = String::new();
+            rwt.write_to(&mut written).unwrap();
+            let encoded = rwt.encode().unwrap();
+            assert_eq!(written, encoded);
+            assert_eq!(encoded.len(), encoded.capacity());
+        }
+
+        // Every length of body, padded or not.
+        for len in 0..6 {
+            let rwt = Rwt::with_payload("x".repeat(len), "secret").unwrap();
+            let encoded = rwt.encode().unwrap();
+            assert_eq!(rwt.to_string(), encoded);
+            assert_eq!(encoded.len(), encoded.capacity());
+        }
+    }
+
+    #[test]
+    fn write_to_matches_encode() {
+        let rwt = create_rwt();
+        let mut buf = String::new();
+        rwt.write_to(&mut buf).unwrap();
+        assert_eq!(rwt.encode().unwrap(), buf);
+        assert_eq!(rwt.encode().unwrap(), rwt.to_string());
+    }
+
+    #[test]
+    fn with_payload_encoded_serializes_once() {
+        use std::cell::Cell;
+
+        struct Counted<'a>(&'a Cell<usize>);
+
+        impl Serialize for Counted<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.0.set(self.0.get() + 1);
+                serializer.serialize_u8(13)
+            }
+        }
+
+        let count = Cell::new(0);
+        let rwt = Rwt::with_payload(Counted(&count), "secret").unwrap();
+        rwt.encode().unwrap();
+        assert_eq!(2, count.get());
+
+        count.set(0);
+        let (rwt, encoded) = Rwt::with_payload_encoded(Counted(&count), "secret").unwrap();
+        assert_eq!(1, count.get());
+        assert_eq!(rwt.encode().unwrap(), encoded);
+        assert!(rwt.is_valid("secret"));
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Extended {
+        jti: String,
+        exp: i64,
+        role: String,
+    }
+
+    fn extend(payload: Payload) -> Extended {
+        Extended {
+            jti: payload.jti,
+            exp: payload.exp,
+            role: "admin".to_owned(),
+        }
+    }
+
+    #[test]
+    fn map_payload() {
+        let rwt = create_rwt().map_payload("other", extend).unwrap();
+        assert_eq!("admin", rwt.payload.role);
+        assert!(rwt.is_valid("other"));
+        assert!(!rwt.is_valid("secret"));
+    }
+
+    #[test]
+    fn map_payload_keeps_key_id() {
+        let rwt = Rwt::with_payload_kid(create_rwt().payload, "secret", "k1").unwrap();
+        let rwt = rwt.map_payload("secret", extend).unwrap();
+        assert_eq!(Some("k1"), rwt.key_id());
+        assert!(rwt.is_valid("secret"));
+    }
+
+    #[test]
+    fn try_map_payload() {
+        let rwt = create_rwt()
+            .try_map_payload("secret", |payload| Ok::<_, Error>(extend(payload)))
+            .unwrap();
+        assert!(rwt.is_valid("secret"));
+
+        let result = create_rwt().try_map_payload("secret", |_| -> crate::Result<Extended> {
+            Err(Error::Format("nope".into()))
+        });
+        match result {
+            Err(Error::Format(message)) => assert_eq!("nope", message),
+            other => panic!("expected Format error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn invalidate_rwt() {
+        let rwt = create_rwt();
+        assert!(!rwt.is_valid("other secret"));
+    }
+
+    #[test]
+    fn verify_rwt() {
+        let rwt = create_rwt();
+        assert!(rwt.verify("secret").is_ok());
+        match rwt.verify("other secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validate_rwt_digest() {
+        use sha2::{Digest, Sha256, Sha512};
+
+        let payload = create_rwt().payload;
+        let rwt = Rwt::with_payload_digest(payload, "secret", Sha512::new()).unwrap();
+        assert!(rwt.is_valid_digest("secret", Sha512::new()));
+        assert!(!rwt.is_valid_digest("other secret", Sha512::new()));
+        assert!(!rwt.is_valid_digest("secret", Sha256::new()));
+        assert!(!rwt.is_valid("secret"));
+    }
+
+    #[test]
+    fn signature_accessors() {
+        let rwt = create_rwt();
+        assert_eq!(
+            "Ir9W3KCkyGNmsPFURs4Sj7aQSkuvcqpQ7kTk4F6wCyU=",
+            rwt.signature()
+        );
+        assert_eq!(32, rwt.signature_bytes().unwrap().len());
+    }
+
+    #[test]
+    fn verify_raw_signature() {
+        let payload = create_rwt().payload;
+        let mut signature = base64::decode("Ir9W3KCkyGNmsPFURs4Sj7aQSkuvcqpQ7kTk4F6wCyU=").unwrap();
+        assert!(verify_signature(&payload, &signature, "secret"));
+        assert!(!verify_signature(&payload, &signature, "other secret"));
+
+        signature[0] ^= 1;
+        assert!(!verify_signature(&payload, &signature, "secret"));
+        assert!(!verify_signature(&payload, &signature[..31], "secret"));
+    }
+
+    #[test]
+    fn debug_redacts_signature() {
+        let rwt = create_rwt();
+        let debug = format!("{:?}", rwt);
+        assert!(!debug.contains(rwt.signature()));
+        assert!(debug.contains("<redacted>"));
+        assert!(debug.contains("this one"));
+        assert!(format!("{:?}", rwt.debug_full()).contains(rwt.signature()));
+    }
+
+    #[test]
+    fn typed_algorithm_round_trip() {
+        fn round_trip<A: SigningAlgorithm + PartialEq + std::fmt::Debug>() {
+            let payload = create_rwt().payload;
+            let rwt = Rwt::<_, A>::with_payload_typed(payload, "secret").unwrap();
+            let encoded = rwt.encode().unwrap();
+            let parsed: Rwt<Payload, A> = encoded.parse().unwrap();
+            assert!(parsed.is_valid("secret"));
+            assert_eq!(rwt, parsed);
+
+            let other: Rwt<Payload> = encoded.parse().unwrap();
+            assert_eq!(
+                A::ALGORITHM == Algorithm::HmacSha256,
+                other.is_valid("secret")
+            );
+        }
+
+        round_trip::<Hs256>();
+        round_trip::<Hs384>();
+        round_trip::<Hs512>();
+    }
+
+    #[test]
+    fn default_algorithm_is_unchanged() {
+        let rwt = Rwt::<_, Hs256>::with_payload_typed(create_rwt().payload, "secret").unwrap();
+        assert_eq!(create_rwt(), rwt);
+        assert_eq!(
+            serde_json::to_string(&create_rwt()).unwrap(),
+            serde_json::to_string(&rwt).unwrap()
+        );
+    }
+
+    #[test]
+    fn verify_batch_reports_each_token() {
+        let valid = create_rwt().encode().unwrap();
+        let tampered = valid.replacen('e', "f", 1);
+        let other = Rwt::with_payload(create_rwt().payload, "other secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+
+        let tokens = [
+            valid.as_str(),
+            tampered.as_str(),
+            "garbage",
+            other.as_str(),
+            &valid,
+        ];
+        let results = Rwt::<Payload>::verify_batch(&tokens, "secret");
+        assert_eq!(5, results.len());
+        assert_eq!(&create_rwt(), results[0].as_ref().unwrap());
+        assert!(results[1].is_err());
+        assert!(matches!(results[2], Err(Error::Format(_))));
+        assert!(matches!(results[3], Err(Error::InvalidSignature)));
+        assert!(results[4].is_ok());
+    }
+
+    #[test]
+    fn validate_rwt_aad() {
+        let rwt = Rwt::with_payload_aad(create_rwt().payload, "secret", "/login").unwrap();
+        assert!(rwt.is_valid_aad("secret", "/login"));
+        assert!(!rwt.is_valid_aad("other secret", "/login"));
+        assert!(!rwt.is_valid_aad("secret", "/logout"));
+        assert!(!rwt.is_valid_aad("secret", ""));
+        assert!(!rwt.is_valid("secret"));
+    }
+
+    #[test]
+    fn aad_framing_is_unambiguous() {
+        let payload = create_rwt().payload;
+        let rwt = Rwt::with_payload_aad(payload, "secret", "ab").unwrap();
+        let body = serde_json::to_vec(&rwt.payload).unwrap();
+
+        // Shifting the boundary between payload and data must not yield the same input.
+        let shifted = super::with_aad(&[&body[..], b"a"].concat(), b"b");
+        assert_ne!(super::with_aad(&body, b"ab"), shifted);
+
+        let plain = Rwt::with_payload(rwt.payload.clone(), "secret").unwrap();
+        assert!(!plain.is_valid_aad("secret", ""));
+    }
+
+    #[test]
+    fn validate_rwt_any() {
+        let rwt = create_rwt();
+        assert!(rwt.is_valid_any(&["old secret", "secret"]));
+        assert!(!rwt.is_valid_any(&["old secret", "other secret"]));
+        assert!(!rwt.is_valid_any::<&str>(&[]));
+    }
+
+    #[test]
+    fn serialize_rwt() {
+        let rwt = create_rwt();
+        assert_eq!(
+            "eyJqdGkiOiJ0aGlzIG9uZSIsImV4cCI6MTN9.\
+                    Ir9W3KCkyGNmsPFURs4Sj7aQSkuvcqpQ7kTk4F6wCyU=",
+            rwt.encode().unwrap()
+        );
+    }
+
+    #[test]
+    fn validate_known_good_rwt() {
+        let rwt = "eyJqdGkiOiJ0aGlzIG9uZSIsImV4cCI6MTN9.\
+                   Ir9W3KCkyGNmsPFURs4Sj7aQSkuvcqpQ7kTk4F6wCyU=";
+        let rwt = rwt.parse::<Rwt<Payload>>().unwrap();
+        assert!(rwt.is_valid("secret"));
+        assert_eq!(rwt, create_rwt());
+    }
+
+    #[test]
+    fn display_rwt() {
+        let rwt = create_rwt();
+        assert_eq!(rwt.to_string(), rwt.encode().unwrap());
+    }
+
+    #[test]
+    fn url_safe_round_trip() {
+        // "???~~~" is "Pz8/fn5+" in standard base64.
+        let payload = Payload {
+            jti: "???~~~".to_owned(),
+            exp: 13,
+        };
+        let rwt = Rwt::with_payload(payload, "secret").unwrap();
+        assert!(rwt.encode().unwrap().contains(&['+', '/'][..]));
+
+        let encoded = rwt.encode_url_safe().unwrap();
+        assert!(!encoded.contains(&['+', '/', '='][..]));
+
+        let parsed = Rwt::<Payload>::parse_url_safe(&encoded).unwrap();
+        assert!(parsed.is_valid("secret"));
+        assert_eq!(parsed, rwt);
+    }
+
+    #[test]
+    fn key_id_round_trip() {
+        let rwt = Rwt::with_payload_kid(create_rwt().payload, "secret", "2020-05").unwrap();
+        let encoded = rwt.encode().unwrap();
+        assert_eq!(3, encoded.split('.').count());
+
+        let parsed = Rwt::<Payload>::parse_verified(&encoded, "secret").unwrap();
+        assert_eq!(Some("2020-05"), parsed.key_id());
+        assert_eq!(parsed, rwt);
+
+        let parsed = Rwt::<Payload>::parse_url_safe(&rwt.encode_url_safe().unwrap()).unwrap();
+        assert_eq!(Some("2020-05"), parsed.key_id());
+        assert!(parsed.is_valid("secret"));
+    }
+
+    #[test]
+    fn empty_key_id_is_rejected() {
+        match Rwt::with_payload_kid(create_rwt().payload, "secret", "") {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+        match Rwt::builder()
+            .payload(create_rwt().payload)
+            .key_id("")
+            .sign("secret")
+        {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+
+        // Anything that signs does read back.
+        let rwt = Rwt::with_payload_kid(create_rwt().payload, "secret", "k").unwrap();
+        let parsed = Rwt::<Payload>::parse_verified(&rwt.encode().unwrap(), "secret").unwrap();
+        assert_eq!(Some("k"), parsed.key_id());
+    }
+
+    #[test]
+    fn two_segment_rwt_has_no_key_id() {
+        let rwt = create_rwt().encode().unwrap();
+        assert_eq!(None, rwt.parse::<Rwt<Payload>>().unwrap().key_id());
+    }
+
+    #[test]
+    fn tampered_key_id_is_invalid() {
+        let rwt = Rwt::with_payload_kid(create_rwt().payload, "secret", "2020-05").unwrap();
+        let encoded = rwt.encode().unwrap();
+        let (_, rest) = encoded.split_once('.').unwrap();
+        let tampered = format!("{}.{}", base64::encode("2020-06"), rest);
+
+        assert!(!tampered.parse::<Rwt<Payload>>().unwrap().is_valid("secret"));
+        match Rwt::<Payload>::parse_verified(&tampered, "secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_borrowed_rwt() {
+        #[derive(Serialize, Deserialize)]
+        struct Borrowed<'a> {
+            jti: &'a str,
+            exp: i64,
+        }
+
+        let rwt = create_rwt().encode().unwrap();
+        let mut buf = Vec::new();
+        let borrowed = Rwt::<Borrowed>::parse_borrowed(&rwt, &mut buf).unwrap();
+
+        assert_eq!("this one", borrowed.payload.jti);
+        assert_eq!(13, borrowed.payload.exp);
+        assert!(borrowed.is_valid("secret"));
+    }
+
+    #[test]
+    fn payload_error_need_only_be_debug() {
+        #[derive(Debug)]
+        enum Rejection {
+            NotAFlag,
+        }
+
+        #[derive(Debug)]
+        struct Flag;
+
+        impl FromStr for Flag {
+            type Err = Rejection;
+
+            fn from_str(_: &str) -> Result<Self, Self::Err> {
+                Err(Rejection::NotAFlag)
+            }
+        }
+
+        let rwt = create_rwt().encode().unwrap();
+        match rwt.parse::<Rwt<Flag>>() {
+            Err(Error::FromStr(message)) => {
+                assert_eq!(
+                    !cfg!(feature = "terse-errors"),
+                    message.contains("NotAFlag")
+                )
+            }
+            other => panic!("expected FromStr error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_json_str_needs_no_from_str() {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Plain {
+            jti: String,
+            exp: i64,
+        }
+
+        let rwt = create_rwt().encode().unwrap();
+        let plain = Rwt::<Plain>::from_json_str(&rwt).unwrap();
+        assert_eq!("this one", plain.payload.jti);
+        assert_eq!(13, plain.payload.exp);
+        assert!(plain.is_valid("secret"));
+
+        match Rwt::<Plain>::from_json_str("garbage") {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn value_round_trip() {
+        let value = serde_json::json!({
+            "sub": "jdoe",
+            "roles": ["admin", "ops"],
+            "exp": 13,
+            "ratio": 0.25,
+            "nested": { "b": null, "a": true },
+        });
+        let encoded = Rwt::with_payload(value.clone(), "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+
+        let parsed = Rwt::parse_value(&encoded).unwrap();
+        assert_eq!(value, parsed.payload);
+        assert!(parsed.is_valid("secret"));
+        assert!(!parsed.is_valid("other secret"));
+        assert_eq!(encoded, parsed.encode().unwrap());
+
+        let typed = create_rwt().encode().unwrap();
+        assert!(!Rwt::parse_value(&typed).unwrap().is_valid("secret"));
+        let untyped = Rwt::<serde_json::Value>::parse_verified(&typed, "secret").unwrap();
+        assert_eq!("this one", untyped.payload["jti"]);
+    }
+
+    #[test]
+    fn unpadded_round_trip() {
+        let rwt = create_rwt();
+        let padded = rwt.encode().unwrap();
+        let unpadded = rwt.encode_unpadded().unwrap();
+        assert!(!unpadded.contains('='));
+        assert!(unpadded.len() < padded.len());
+
+        for encoded in &[padded, unpadded] {
+            let parsed = encoded.parse::<Rwt<Payload>>().unwrap();
+            assert!(parsed.is_valid("secret"));
+            assert_eq!(parsed, rwt);
+            assert!(Rwt::<Payload>::parse_verified(encoded, "secret").is_ok());
+        }
+    }
+
+    #[test]
+    fn try_from_str() -> crate::Result<()> {
+        let encoded = create_rwt().encode()?;
+        let rwt: Rwt<Payload> = encoded.as_str().try_into()?;
+        assert_eq!(rwt, create_rwt());
+
+        let rwt: Rwt<Payload> = encoded.try_into()?;
+        assert_eq!(rwt, create_rwt());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_body_and_signature() {
+        let rwt = create_rwt();
+        let encoded = rwt.encode().unwrap();
+        assert_eq!(
+            r#"{"jti":"this one","exp":13}"#,
+            decode_body(&encoded).unwrap()
+        );
+        assert_eq!(
+            rwt.signature_bytes().unwrap(),
+            decode_signature(&encoded).unwrap()
+        );
+
+        let rwt = Rwt::with_payload_kid(rwt.payload, "secret", "2020-05").unwrap();
+        let encoded = rwt.encode().unwrap();
+        assert_eq!(
+            r#"{"jti":"this one","exp":13}"#,
+            decode_body(&encoded).unwrap()
+        );
+        assert_eq!(
+            rwt.signature_bytes().unwrap(),
+            decode_signature(&encoded).unwrap()
+        );
+    }
+
+    #[test]
+    fn parts_borrow_from_token() {
+        let encoded = create_rwt().encode().unwrap();
+        let (body, signature) = parts(&encoded).unwrap();
+        assert_eq!(encoded, format!("{}.{}", body, signature));
+        assert!(encoded.as_bytes().as_ptr_range().contains(&body.as_ptr()));
+        assert_eq!(create_rwt().signature(), signature);
+
+        let keyed = Rwt::with_payload_kid(create_rwt().payload, "secret", "2020-05").unwrap();
+        let encoded = keyed.encode().unwrap();
+        assert_eq!((body, keyed.signature()), parts(&encoded).unwrap());
+    }
+
+    #[test]
+    fn parts_reports_missing_segments() {
+        for token in &["bm8gZG90", "bm8gc2lnbmF0dXJl."] {
+            match parts(token) {
+                Err(Error::Format(_)) => (),
+                other => panic!("expected Format error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn decode_body_reports_errors() {
+        match decode_body("!!!!.signature") {
+            Err(Error::Base64(_)) => (),
+            other => panic!("expected Base64 error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn decode_base64_decodes_body() {
+        let encoded = create_rwt().encode().unwrap();
+        assert_eq!(decode_body(&encoded).ok(), super::decode_base64(&encoded));
+    }
+
+    #[test]
+    fn parse_with_limit() {
+        let encoded = create_rwt().encode().unwrap();
+        let rwt = Rwt::<Payload>::parse_with_limit(&encoded, encoded.len()).unwrap();
+        assert_eq!(rwt, create_rwt());
+        assert!(Rwt::<Payload>::parse_verified_with_limit(&encoded, "secret", 8192).is_ok());
+        assert!(Rwt::<Payload>::parse_with_limit(&encoded, encoded.len() - 1).is_err());
+    }
+
+    #[test]
+    fn parse_with_limit_rejects_oversized_input() {
+        // Not even valid base64, so this would fail differently if it got as far as decoding.
+        let huge = "!".repeat(1 << 20);
+        match Rwt::<Payload>::parse_with_limit(&huge, 8192) {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+        match Rwt::<Payload>::parse_verified_with_limit(&huge, "secret", 8192) {
+            Err(Error::Format(_)) => (),
+            other => panic!("expected Format error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserialize_rwt() {
+        let rwt = create_rwt().encode().unwrap();
+        let rwt = rwt.parse::<Rwt<Payload>>().unwrap();
+        assert_eq!(rwt, create_rwt());
+    }
+
+    #[test]
+    fn parse_verified_rwt() {
+        let rwt = create_rwt().encode().unwrap();
+        let rwt = Rwt::<Payload>::parse_verified(&rwt, "secret").unwrap();
+        assert_eq!(rwt, create_rwt());
+    }
+
+    #[test]
+    fn parse_verified_rejects_tampered_body() {
+        let rwt = create_rwt().encode().unwrap();
+        let signature = rwt.split('.').nth(1).unwrap();
+        let body = base64::encode(r#"{"jti":"this one","exp":99}"#);
+        let tampered = format!("{}.{}", body, signature);
+
+        match Rwt::<Payload>::parse_verified(&tampered, "secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_str_pins_the_algorithm() {
+        use super::verify_str_alg;
+
+        let header = Header {
+            alg: Algorithm::HmacSha512,
+            typ: Some("rwt".to_owned()),
+            kid: None,
+        };
+        let encoded = Rwt::with_header(create_rwt().payload, header, "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+        match verify_str(&encoded, "secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+        verify_str_alg(&encoded, "secret", Algorithm::HmacSha512).unwrap();
+        match verify_str_alg(
+            &create_rwt().encode().unwrap(),
+            "secret",
+            Algorithm::HmacSha512,
+        ) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sha512_rwt_is_not_valid_as_sha256() {
+        let payload = Payload {
+            jti: "this one".to_owned(),
+            exp: 13,
+        };
+        let rwt = Rwt::with_payload_alg(payload, "secret", Algorithm::HmacSha512).unwrap();
+        assert!(rwt.verify_with_alg("secret", Algorithm::HmacSha512).is_ok());
+
+        // The token's own header says SHA512, but an `Rwt<Payload>` is checked with SHA256.
+        assert!(!rwt.is_valid("secret"));
+        assert!(!rwt.is_valid_any(&["secret"]));
+        match rwt.verify("secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+
+        let encoded = rwt.encode().unwrap();
+        assert!(!encoded.parse::<Rwt<Payload>>().unwrap().is_valid("secret"));
+        assert!(Rwt::<Payload>::parse_verified(&encoded, "secret").is_err());
+        assert_eq!(
+            Rwt::<Payload>::parse_verified_alg(&encoded, "secret", Algorithm::HmacSha512).unwrap(),
+            rwt
+        );
     }
 
     fn create_rwt() -> Rwt<Payload> {
@@ -0,0 +1,67 @@
+use crate::{Error, Result, Rwt, SecretKey, Signable, SigningAlgorithm};
+use std::sync::Arc;
+
+impl<T, A> Rwt<T, A>
+where
+    T: Signable + Send + Sync + 'static,
+    A: SigningAlgorithm + Send + Sync + 'static,
+{
+    /// Verify the token on tokio's blocking thread pool.
+    ///
+    /// This is `verify` for payloads big enough that serializing and hashing them would hold up
+    /// the executor. The token comes in an `Arc`, so handing it to the blocking task costs a
+    /// reference count rather than a copy of the payload; keep a clone of the `Arc` to go on using
+    /// the token afterward. The task gets its own copy of the secret, so it never borrows anything
+    /// from the caller. That makes the future safe to drop: the task finishes on its own, nobody
+    /// hears the answer, and its copy of the secret is zeroed with it, as with any `SecretKey`.
+    ///
+    /// Errors are those of `verify`. If the runtime shuts down before the task can run, the
+    /// token is rejected with `Error::InvalidSignature`; this fails closed.
+    #[must_use = "verification result must be checked"]
+    pub async fn verify_async<S: AsRef<[u8]>>(self: Arc<Self>, secret: S) -> Result<()> {
+        let secret = SecretKey::new(secret.as_ref());
+        let task = tokio::task::spawn_blocking(move || self.verify(&secret));
+        match task.await {
+            Ok(result) => result,
+            Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+            Err(_) => Err(Error::InvalidSignature),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{Error, Rwt};
+    use std::sync::Arc;
+
+    fn token() -> Arc<Rwt<String>> {
+        Arc::new(Rwt::with_payload("x".repeat(1 << 20), "secret").unwrap())
+    }
+
+    #[tokio::test]
+    async fn verify_async() {
+        let rwt = token();
+        rwt.clone().verify_async("secret").await.unwrap();
+        match rwt.verify_async("other secret").await {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_verification_leaves_token_alone() {
+        let rwt = token();
+        let original = (*rwt).clone();
+
+        // The verification is started, then abandoned when the other branch wins.
+        tokio::select! {
+            biased;
+            _ = tokio::task::yield_now() => (),
+            _ = rwt.clone().verify_async("secret") => panic!("verification should have been dropped"),
+        }
+
+        assert_eq!(original, *rwt);
+        assert!(rwt.is_valid("secret"));
+        rwt.verify_async("secret").await.unwrap();
+    }
+}
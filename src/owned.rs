@@ -0,0 +1,163 @@
+use crate::{check_signature, split_parts, Algorithm, Error, Header, Result};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Deserialize;
+use serde_json as json;
+use yoke::{Yoke, Yokeable};
+
+/// A token that owns its decoded body, with a payload borrowing from it.
+///
+/// `Rwt::parse_borrowed` makes the caller keep the buffer alive; this keeps it for you, so a
+/// payload full of `&str` fields can be parsed once and passed around like any other value. The
+/// payload type is named with a `'static` lifetime, as in `OwnedRwt<Payload<'static>>`, and must
+/// derive `yoke::Yokeable`, which is what lets it borrow from its own token safely. `payload`
+/// hands it back with the lifetime it really has.
+///
+/// The body is kept exactly as it arrived, so the signature is checked against those bytes
+/// rather than a re-serialized payload. Only the default algorithm, `Algorithm::HmacSha256`, is
+/// supported.
+pub struct OwnedRwt<T: for<'a> Yokeable<'a>> {
+    payload: Yoke<T, Box<[u8]>>,
+    signature: String,
+    header: Header,
+}
+
+impl<T> OwnedRwt<T>
+where
+    T: for<'a> Yokeable<'a>,
+    for<'a> <T as Yokeable<'a>>::Output: Deserialize<'a>,
+{
+    /// Parse a token, keeping its body.
+    ///
+    /// Like `FromStr`, **this does not check the signature**; call `is_valid` afterward.
+    pub fn parse(s: &str) -> Result<OwnedRwt<T>> {
+        let (body, signature, header) = decode(s)?;
+        OwnedRwt::attach(body, signature, header)
+    }
+
+    /// Parse a token and verify its signature in a single step.
+    ///
+    /// The signature is checked before the payload is deserialized, so a forgery never gets as
+    /// far as serde. A bad signature yields `Error::InvalidSignature`.
+    pub fn parse_verified<S: AsRef<[u8]>>(s: &str, secret: S) -> Result<OwnedRwt<T>> {
+        let (body, signature, header) = decode(s)?;
+        if header.alg != Algorithm::default() {
+            return Err(Error::InvalidSignature);
+        }
+        let input = header.signing_input(&body)?;
+        check_signature(header.alg, &input, &signature, secret.as_ref())?;
+        OwnedRwt::attach(body, signature, header)
+    }
+
+    fn attach(body: Vec<u8>, signature: String, header: Header) -> Result<OwnedRwt<T>> {
+        let body = body.into_boxed_slice();
+        Ok(OwnedRwt {
+            payload: Yoke::try_attach_to_cart(body, |body| json::from_slice(body))?,
+            signature,
+            header,
+        })
+    }
+}
+
+impl<T: for<'a> Yokeable<'a>> OwnedRwt<T> {
+    /// The token's payload, borrowing from the token.
+    pub fn payload(&self) -> &<T as Yokeable<'_>>::Output {
+        self.payload.get()
+    }
+
+    /// The decoded body of the token: the very bytes that were signed.
+    pub fn body(&self) -> &[u8] {
+        self.payload.backing_cart()
+    }
+
+    /// The id of the key used to sign this token, if it has one.
+    pub fn key_id(&self) -> Option<&str> {
+        self.header.kid.as_deref()
+    }
+
+    /// The token's header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The token's signature, in standard base64.
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Validate the token's signature against its body, as it arrived.
+    ///
+    /// As with `parse_verified`, a header naming any algorithm but the default is rejected.
+    #[must_use = "verification result must be checked"]
+    pub fn is_valid<S: AsRef<[u8]>>(&self, secret: S) -> bool {
+        if self.header.alg != Algorithm::default() {
+            return false;
+        }
+        match self.header.signing_input(self.body()) {
+            Err(_) => false,
+            Ok(input) => {
+                check_signature(self.header.alg, &input, &self.signature, secret.as_ref()).is_ok()
+            }
+        }
+    }
+}
+
+/// Split a token into its decoded body, signature, and header.
+fn decode(s: &str) -> Result<(Vec<u8>, String, Header)> {
+    let segments = split_parts(s)?;
+    let (header, body) = segments.decode(base64::STANDARD, Algorithm::default())?;
+    Ok((body, segments.signature(base64::STANDARD)?, header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OwnedRwt;
+    use crate::{Error, Rwt};
+    use serde::{Deserialize, Serialize};
+    use yoke::Yokeable;
+
+    #[derive(Serialize, Deserialize, Yokeable)]
+    struct Borrowed<'a> {
+        jti: &'a str,
+        exp: i64,
+    }
+
+    fn encoded() -> String {
+        let payload = Borrowed {
+            jti: "this one",
+            exp: 13,
+        };
+        Rwt::with_payload(payload, "secret")
+            .unwrap()
+            .encode()
+            .unwrap()
+    }
+
+    #[test]
+    fn payload_borrows_from_token() {
+        let encoded = encoded();
+        let rwt = OwnedRwt::<Borrowed<'static>>::parse(&encoded).unwrap();
+        drop(encoded);
+
+        let payload = rwt.payload();
+        assert_eq!("this one", payload.jti);
+        assert_eq!(13, payload.exp);
+        assert!(rwt.body().as_ptr_range().contains(&payload.jti.as_ptr()));
+        assert!(rwt.is_valid("secret"));
+        assert!(!rwt.is_valid("other secret"));
+    }
+
+    #[test]
+    fn parse_verified_rejects_bad_signature() {
+        let encoded = encoded();
+        let rwt = OwnedRwt::<Borrowed<'static>>::parse_verified(&encoded, "secret").unwrap();
+        assert_eq!("this one", rwt.payload().jti);
+
+        match OwnedRwt::<Borrowed<'static>>::parse_verified(&encoded, "other secret") {
+            Err(Error::InvalidSignature) => (),
+            Err(other) => panic!("expected InvalidSignature, got {:?}", other),
+            Ok(_) => panic!("expected InvalidSignature, got a token"),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! Everything a typical user needs, in one glob import.
+//!
+//! ```
+//! use rwt::prelude::*;
+//!
+//! fn sign(secret: &SecretKey) -> Result<Rwt<&'static str>> {
+//!     Rwt::builder()
+//!         .payload("my payload")
+//!         .algorithm(Algorithm::HmacSha512)
+//!         .sign(secret)
+//! }
+//!
+//! let secret = SecretKey::new("secret");
+//! let token = sign(&secret).unwrap();
+//! assert!(token.verify_with_alg(&secret, Algorithm::HmacSha512).is_ok());
+//! ```
+
+pub use crate::{
+    Algorithm, Clock, Error, Expiring, ExpiringMut, Header, IssuedAt, IssuedAtMut, NotBefore,
+    Result, Rwt, RwtBuilder, SecretKey, Signable,
+};
+#[cfg(feature = "claims")]
+pub use crate::{Audience, Claims};
@@ -0,0 +1,100 @@
+use crate::{Error, Format, Header, Result, Rwt};
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+/// A payload of JSON that is signed and encoded exactly as given, without ever being parsed.
+///
+/// The JSON is checked for validity when the payload is created, and whitespace and all survive
+/// the trip into the token untouched.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RawPayload(Box<RawValue>);
+
+impl RawPayload {
+    /// Wrap a string of JSON, failing with `Error::Json` if it isn't valid.
+    pub fn new(json: String) -> Result<RawPayload> {
+        Ok(RawPayload(RawValue::from_string(json)?))
+    }
+
+    /// The JSON, exactly as given.
+    pub fn as_str(&self) -> &str {
+        self.0.get()
+    }
+}
+
+impl FromStr for RawPayload {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        RawPayload::new(s.to_owned())
+    }
+}
+
+impl PartialEq for RawPayload {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for RawPayload {}
+
+impl Hash for RawPayload {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl Rwt<RawPayload> {
+    /// Create a web token from a payload which is already JSON.
+    ///
+    /// This is for signing bodies you have no reason to parse, like one forwarded from another
+    /// service. The bytes are checked once, up front, and rejected with `Error::Encoding` if they
+    /// aren't UTF-8 or `Error::Json` if they aren't JSON. Beyond that, they're signed verbatim.
+    pub fn with_raw_payload<S: AsRef<[u8]>>(json: &[u8], secret: S) -> Result<Rwt<RawPayload>> {
+        let payload = RawPayload::new(core::str::from_utf8(json)?.to_owned())?;
+        Rwt::sign(payload, Header::default(), Format::Json, secret.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawPayload;
+    use crate::{Error, Rwt};
+
+    const JSON: &[u8] = br#"{ "sub": "jdoe",  "roles": ["admin"] }"#;
+
+    #[test]
+    fn sign_raw_payload() {
+        let rwt = Rwt::with_raw_payload(JSON, "secret").unwrap();
+        assert!(rwt.is_valid("secret"));
+        assert!(!rwt.is_valid("other secret"));
+        assert_eq!(JSON, rwt.payload.as_str().as_bytes());
+    }
+
+    #[test]
+    fn raw_payload_is_signed_verbatim() {
+        let rwt = Rwt::with_raw_payload(JSON, "secret").unwrap();
+        let encoded = rwt.encode().unwrap();
+        assert_eq!(JSON, crate::decode_body(&encoded).unwrap().as_bytes());
+
+        let parsed = Rwt::<RawPayload>::parse_verified(&encoded, "secret").unwrap();
+        assert_eq!(rwt, parsed);
+    }
+
+    #[test]
+    fn reject_invalid_raw_payload() {
+        match Rwt::with_raw_payload(b"{ not json", "secret") {
+            Err(Error::Json(_)) => (),
+            other => panic!("expected Json error, got {:?}", other),
+        }
+        match Rwt::with_raw_payload(b"\"\xff\"", "secret") {
+            Err(Error::Encoding(_)) => (),
+            other => panic!("expected Encoding error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Serialize a token field as its encoded string, without wrapping it in `RwtString`.
+//!
+//! ```
+//! # use rwt::Rwt;
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Response {
+//!     #[serde(with = "rwt::serde_compact")]
+//!     token: Rwt<String>,
+//! }
+//! ```
+//!
+//! As with `RwtString`, the token goes out as `encode` writes it and comes back in through
+//! `FromStr`, whose errors become serde errors. Nothing checks the signature on the way in.
+
+use crate::{Rwt, RwtString, Signable, SigningAlgorithm};
+use core::fmt::Debug;
+use core::str::FromStr;
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Deserializer};
+
+pub fn serialize<T, A, S>(rwt: &Rwt<T, A>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Signable,
+    S: Serializer,
+{
+    let encoded = rwt.encode().map_err(ser::Error::custom)?;
+    serializer.serialize_str(&encoded)
+}
+
+pub fn deserialize<'de, T, E, A, D>(deserializer: D) -> Result<Rwt<T, A>, D::Error>
+where
+    A: SigningAlgorithm,
+    E: Debug,
+    T: FromStr<Err = E>,
+    D: Deserializer<'de>,
+{
+    RwtString::deserialize(deserializer).map(RwtString::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Rwt;
+    use serde::{Deserialize, Serialize};
+    use std::str::FromStr;
+
+    #[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+    struct Payload {
+        sub: String,
+    }
+
+    impl FromStr for Payload {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Response {
+        #[serde(with = "crate::serde_compact")]
+        token: Rwt<Payload>,
+    }
+
+    #[test]
+    fn field_serializes_as_encoded_string() {
+        let token = Rwt::with_payload(Payload { sub: "jdoe".into() }, "secret").unwrap();
+        let json = serde_json::to_string(&Response {
+            token: token.clone(),
+        })
+        .unwrap();
+        assert_eq!(
+            format!(r#"{{"token":"{}"}}"#, token.encode().unwrap()),
+            json
+        );
+
+        let response: Response = serde_json::from_str(&json).unwrap();
+        assert_eq!(token, response.token);
+        assert!(response.token.is_valid("secret"));
+    }
+
+    #[test]
+    fn parse_errors_are_serde_errors() {
+        let result = serde_json::from_str::<Response>(r#"{"token":"not a token"}"#);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Missing signature"));
+    }
+}
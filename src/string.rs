@@ -0,0 +1,109 @@
+use crate::{Hs256, Rwt, Signable, SigningAlgorithm};
+use alloc::string::String;
+use core::fmt::Debug;
+use core::ops::Deref;
+use core::str::FromStr;
+use serde::de::{self, Deserializer};
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Serialize};
+
+/// A token that serializes as its encoded string.
+///
+/// The derived impls on `Rwt` write out the payload and signature as a struct, which is what you
+/// want for storing a token and not at all what you want in an API response. Wrap the token in
+/// one of these and it goes out as `encode` would write it, and comes back in through `FromStr`,
+/// so `{ "token": "xxx.xxx" }` is exactly what it looks like. As with `FromStr`, nothing checks
+/// the signature on the way in.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RwtString<T, A = Hs256>(pub Rwt<T, A>);
+
+impl<T, A> RwtString<T, A> {
+    pub fn into_inner(self) -> Rwt<T, A> {
+        self.0
+    }
+}
+
+impl<T, A> From<Rwt<T, A>> for RwtString<T, A> {
+    fn from(rwt: Rwt<T, A>) -> Self {
+        RwtString(rwt)
+    }
+}
+
+impl<T, A> Deref for RwtString<T, A> {
+    type Target = Rwt<T, A>;
+
+    fn deref(&self) -> &Rwt<T, A> {
+        &self.0
+    }
+}
+
+impl<T: Signable, A> Serialize for RwtString<T, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let encoded = self.0.encode().map_err(ser::Error::custom)?;
+        serializer.serialize_str(&encoded)
+    }
+}
+
+impl<'de, T, E, A> Deserialize<'de> for RwtString<T, A>
+where
+    A: SigningAlgorithm,
+    E: Debug,
+    T: FromStr<Err = E>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        encoded.parse().map(RwtString).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RwtString;
+    use crate::Rwt;
+    use serde::{Deserialize, Serialize};
+    use std::str::FromStr;
+
+    #[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+    struct Payload {
+        sub: String,
+    }
+
+    impl FromStr for Payload {
+        type Err = &'static str;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s).map_err(|_| "Sorry, Charlie.")
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Response {
+        token: RwtString<Payload>,
+    }
+
+    fn create_rwt() -> Rwt<Payload> {
+        let payload = Payload { sub: "jdoe".into() };
+        Rwt::with_payload(payload, "secret").unwrap()
+    }
+
+    #[test]
+    fn serializes_as_encoded_string() {
+        let rwt = create_rwt();
+        let encoded = rwt.encode().unwrap();
+        let response = Response {
+            token: rwt.clone().into(),
+        };
+
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(format!(r#"{{"token":"{}"}}"#, encoded), json);
+
+        let response: Response = serde_json::from_str(&json).unwrap();
+        assert_eq!(rwt, response.token.into_inner());
+    }
+
+    #[test]
+    fn rejects_malformed_string() {
+        let result = serde_json::from_str::<Response>(r#"{"token":"not a token"}"#);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! `proptest` strategies for tokens and the things that go in them.
+//!
+//! These are what the crate's own property tests run on, and they're here so you can throw the
+//! same weather at your own payloads: hand `tokens` a strategy for your payload type and it will
+//! sign each one with a random secret. Claim values run to the awkward end of things, like empty
+//! strings, control characters, astral-plane unicode, and timestamps at the ends of `i64`.
+
+use crate::{Audience, Claims, Rwt, Signable};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+
+/// A string fit for a claim: empty, plain, or anything at all.
+pub fn claim_string() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        "[a-z0-9-]{1,16}",
+        "[\\x00-\\x1f\"\\\\]{1,8}",
+        any::<String>(),
+    ]
+}
+
+/// A Unix timestamp, with a thumb on the scale for the extremes.
+pub fn timestamp() -> impl Strategy<Value = i64> {
+    prop_oneof![
+        Just(0),
+        Just(i64::MIN),
+        Just(i64::MAX),
+        i64::MAX - 1_000..=i64::MAX,
+        any::<i64>(),
+    ]
+}
+
+pub fn audience() -> impl Strategy<Value = Audience> {
+    prop_oneof![
+        claim_string().prop_map(Audience::One),
+        vec(claim_string(), 0..4).prop_map(Audience::Many),
+    ]
+}
+
+/// A set of registered claims, any of which may be missing.
+pub fn claims() -> impl Strategy<Value = Claims> {
+    (
+        option::of(claim_string()),
+        option::of(claim_string()),
+        option::of(audience()),
+        option::of(timestamp()),
+        option::of(timestamp()),
+        option::of(timestamp()),
+        option::of(claim_string()),
+    )
+        .prop_map(|(sub, iss, aud, exp, nbf, iat, jti)| Claims {
+            sub,
+            iss,
+            aud,
+            exp,
+            nbf,
+            iat,
+            jti,
+        })
+}
+
+/// A secret of any length, including none.
+pub fn secret() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..100)
+}
+
+/// Tokens signed over payloads drawn from `payload`, along with the secret that signed each.
+pub fn tokens<T, P>(payload: P) -> impl Strategy<Value = (Rwt<T>, Vec<u8>)>
+where
+    T: Signable + Debug,
+    P: Strategy<Value = T>,
+{
+    (payload, secret()).prop_map(|(payload, secret)| {
+        let rwt = Rwt::with_payload(payload, &secret).expect("payload should serialize");
+        (rwt, secret)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{claims, secret, tokens};
+    use crate::{Claims, Rwt};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn encoded_tokens_round_trip((rwt, secret) in tokens(claims())) {
+            for encoded in [rwt.encode(), rwt.encode_unpadded()] {
+                let parsed: Rwt<Claims> = encoded.unwrap().parse().unwrap();
+                prop_assert!(parsed.is_valid(&secret));
+                prop_assert_eq!(&rwt, &parsed);
+            }
+            let parsed = Rwt::<Claims>::parse_url_safe(&rwt.encode_url_safe().unwrap()).unwrap();
+            prop_assert_eq!(&rwt, &parsed);
+        }
+
+        #[test]
+        fn other_secrets_never_verify((rwt, secret) in tokens(claims()), other in secret()) {
+            prop_assume!(other != secret);
+            prop_assert!(!rwt.is_valid(&other));
+            prop_assert!(Rwt::<Claims>::parse_verified(&rwt.encode().unwrap(), &other).is_err());
+        }
+
+        #[test]
+        fn tampered_payloads_never_verify(
+            (rwt, secret) in tokens(claims()),
+            tampered in claims(),
+        ) {
+            prop_assume!(tampered != rwt.payload);
+            let forged = Rwt::new_unsigned(tampered)
+                .encode()
+                .map(|body| format!("{}{}", body, rwt.signature()))
+                .unwrap();
+            prop_assert!(Rwt::<Claims>::parse_verified(&forged, &secret).is_err());
+        }
+    }
+}
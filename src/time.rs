@@ -0,0 +1,489 @@
+use crate::{Error, Hs256, Result, Rwt, Signable, SigningAlgorithm};
+use core::cmp::Ordering;
+
+/// A payload carrying an expiration time.
+///
+/// Implement this for your payload to get access to `Rwt::is_expired`.
+pub trait Expiring {
+    /// The Unix timestamp, in seconds, after which the token should no longer be accepted.
+    fn expiry(&self) -> i64;
+}
+
+/// A payload carrying a time before which it is not yet valid.
+///
+/// Implement this for your payload to get access to `Rwt::is_active`.
+pub trait NotBefore {
+    /// The Unix timestamp, in seconds, before which the token should not be accepted.
+    fn not_before(&self) -> i64;
+}
+
+/// A payload carrying the time at which it was issued.
+///
+/// Implement this for your payload to get access to `Rwt::older_than`.
+pub trait IssuedAt {
+    /// The Unix timestamp, in seconds, at which the token was issued.
+    fn issued_at(&self) -> i64;
+}
+
+/// A payload whose issued-at time can be set.
+///
+/// Implement this for your payload to have `RwtBuilder::issued_at` stamp it for you.
+pub trait IssuedAtMut: IssuedAt {
+    fn set_issued_at(&mut self, iat: i64);
+}
+
+/// A payload whose expiration time can be set.
+///
+/// Implement this for your payload to have `RwtBuilder::expires_in` stamp it for you.
+pub trait ExpiringMut: Expiring {
+    fn set_expiry(&mut self, exp: i64);
+}
+
+/// A source of the current time.
+///
+/// The time-based checks on `Rwt` accept any clock. A bare `i64` is a clock stuck at that
+/// timestamp, so `token.is_expired(now)` works just as it always has; `SystemClock` reads the real
+/// time, and `FixedClock` is there for tests.
+pub trait Clock {
+    /// The current time as a Unix timestamp in seconds.
+    fn now(&self) -> i64;
+}
+
+impl Clock for i64 {
+    fn now(&self) -> i64 {
+        *self
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> i64 {
+        (**self).now()
+    }
+}
+
+/// A clock that always reads the same time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> i64 {
+        self.0
+    }
+}
+
+/// The system clock.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        system_now()
+    }
+}
+
+/// The current time according to the system clock, as a Unix timestamp in seconds.
+#[cfg(feature = "std")]
+fn system_now() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+impl<T: Expiring, A> Rwt<T, A> {
+    /// Check whether the token has expired.
+    ///
+    /// The current time comes from `clock`, which may be a plain Unix timestamp in seconds or any
+    /// other `Clock`. Signing and verifying never read the time, which keeps the core usable
+    /// without `std`; the only other reader is `RwtBuilder::expires_in`, through the builder's own
+    /// `Clock`. A token is expired only once the current time has passed its expiry, so a
+    /// token whose expiry is exactly now is still good.
+    ///
+    /// This says nothing about whether the token is authentic. Check the signature, too.
+    #[must_use = "verification result must be checked"]
+    pub fn is_expired(&self, clock: impl Clock) -> bool {
+        clock.now() > self.payload.expiry()
+    }
+
+    /// Check the token's expiry, yielding `Error::Expired` if it has passed.
+    ///
+    /// This is `is_expired` for callers who would rather use `?`.
+    #[must_use = "verification result must be checked"]
+    pub fn check_expiry(&self, clock: impl Clock) -> Result<()> {
+        if self.is_expired(clock) {
+            Err(Error::Expired)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T: Expiring + Signable, A: SigningAlgorithm> Rwt<T, A> {
+    /// Check that the token is good right now: authentic and unexpired.
+    ///
+    /// This is `verify` followed by `check_expiry`, for those of us who would forget the second
+    /// half. A bad signature yields `Error::InvalidSignature` and a stale token `Error::Expired`.
+    /// The signature is checked first, so an expired forgery is still a forgery. Payloads with a
+    /// not-before time want `is_usable_window`, which checks that, too.
+    #[must_use = "verification result must be checked"]
+    pub fn is_usable<S: AsRef<[u8]>>(&self, secret: S, clock: impl Clock) -> Result<()> {
+        self.verify(secret)?;
+        self.check_expiry(clock)
+    }
+}
+
+impl<T: Expiring + NotBefore + Signable, A: SigningAlgorithm> Rwt<T, A> {
+    /// Check that the token is good right now: authentic, active, and unexpired.
+    ///
+    /// This is `is_usable` for payloads with a not-before time as well as an expiry, so that the
+    /// whole window is checked in one call. A bad signature yields `Error::InvalidSignature`, a
+    /// token whose time hasn't come `Error::NotYetValid`, and a stale one `Error::Expired`.
+    /// `leeway` is as for `check_active`, and applies only to the not-before time.
+    #[must_use = "verification result must be checked"]
+    pub fn is_usable_window<S: AsRef<[u8]>>(
+        &self,
+        secret: S,
+        clock: impl Clock,
+        leeway: i64,
+    ) -> Result<()> {
+        self.verify(secret)?;
+        self.check_active(&clock, leeway)?;
+        self.check_expiry(clock)
+    }
+}
+
+impl<T: NotBefore, A> Rwt<T, A> {
+    /// Check whether the token has become valid.
+    ///
+    /// The current time comes from `clock`, as with `is_expired`. `leeway` absorbs clock skew
+    /// between issuer and verifier: the token is active once `now + leeway >= nbf`. This does not
+    /// check expiry, so a caller wanting both should ask both questions:
+    /// `token.is_active(&clock, leeway) && !token.is_expired(&clock)`.
+    #[must_use = "verification result must be checked"]
+    pub fn is_active(&self, clock: impl Clock, leeway: i64) -> bool {
+        clock.now().saturating_add(leeway) >= self.payload.not_before()
+    }
+
+    /// Check that the token has become valid, yielding `Error::NotYetValid` if it hasn't.
+    ///
+    /// This is `is_active` for callers who would rather use `?`.
+    #[must_use = "verification result must be checked"]
+    pub fn check_active(&self, clock: impl Clock, leeway: i64) -> Result<()> {
+        if self.is_active(clock, leeway) {
+            Ok(())
+        } else {
+            Err(Error::NotYetValid)
+        }
+    }
+}
+
+impl<T: IssuedAt, A> Rwt<T, A> {
+    /// Check whether the token was issued more than `max_age` seconds before the current time.
+    ///
+    /// This is for services that reject old tokens regardless of their expiry. A token from the
+    /// future (issued after now) is not older than anything.
+    #[must_use = "verification result must be checked"]
+    pub fn older_than(&self, clock: impl Clock, max_age: i64) -> bool {
+        let now = clock.now();
+        let iat = self.payload.issued_at();
+        iat <= now && now.saturating_sub(iat) > max_age
+    }
+}
+
+impl<T: ExpiringMut + Signable, A> Rwt<T, A> {
+    /// Re-issue the token with a new expiry.
+    ///
+    /// Everything else about the payload is kept, as are the algorithm, key id, and format. The
+    /// new token is signed afresh with `secret`; the old signature is thrown away and never
+    /// carried over. As with `map_payload`, nothing here checks the original, so verify it first.
+    pub fn refresh<S: AsRef<[u8]>>(self, secret: S, new_exp: i64) -> Result<Rwt<T, A>> {
+        let mut payload = self.payload;
+        payload.set_expiry(new_exp);
+        Rwt::sign(payload, self.header, self.format, secret.as_ref())
+    }
+}
+
+/// A token ordered by its expiry, soonest first.
+///
+/// `Rwt` has no `Ord` of its own, since comparing payloads and signatures says nothing useful.
+/// This compares expiries and nothing else, so two tokens expiring at the same moment are equal
+/// as far as it's concerned. Wrap it in `Reverse` for a `BinaryHeap` that pops the soonest to
+/// expire.
+#[derive(Clone, Debug)]
+pub struct ByExpiry<T, A = Hs256>(pub Rwt<T, A>);
+
+impl<T: Expiring, A> PartialEq for ByExpiry<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Expiring, A> Eq for ByExpiry<T, A> {}
+
+impl<T: Expiring, A> PartialOrd for ByExpiry<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Expiring, A> Ord for ByExpiry<T, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.payload.expiry().cmp(&other.0.payload.expiry())
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::{ByExpiry, Expiring, ExpiringMut, FixedClock, IssuedAt, NotBefore};
+    use crate::{Error, Rwt};
+    use serde::Serialize;
+
+    #[derive(Clone, Serialize)]
+    struct Payload {
+        exp: i64,
+    }
+
+    #[derive(Serialize)]
+    struct Scheduled {
+        nbf: i64,
+    }
+
+    impl NotBefore for Scheduled {
+        fn not_before(&self) -> i64 {
+            self.nbf
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Window {
+        nbf: i64,
+        exp: i64,
+    }
+
+    impl NotBefore for Window {
+        fn not_before(&self) -> i64 {
+            self.nbf
+        }
+    }
+
+    impl Expiring for Window {
+        fn expiry(&self) -> i64 {
+            self.exp
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Issued {
+        iat: i64,
+    }
+
+    impl IssuedAt for Issued {
+        fn issued_at(&self) -> i64 {
+            self.iat
+        }
+    }
+
+    impl Expiring for Payload {
+        fn expiry(&self) -> i64 {
+            self.exp
+        }
+    }
+
+    impl ExpiringMut for Payload {
+        fn set_expiry(&mut self, exp: i64) {
+            self.exp = exp;
+        }
+    }
+
+    #[test]
+    fn is_expired_after_exp() {
+        let rwt = Rwt::with_payload(Payload { exp: 13 }, "secret").unwrap();
+        assert!(!rwt.is_expired(12));
+        assert!(!rwt.is_expired(13));
+        assert!(rwt.is_expired(14));
+    }
+
+    #[test]
+    fn is_expired_ignores_signature() {
+        let rwt = Rwt::with_payload(Payload { exp: 13 }, "secret").unwrap();
+        assert!(!rwt.is_valid("other secret"));
+        assert!(!rwt.is_expired(13));
+    }
+
+    #[test]
+    fn check_expiry_yields_expired() {
+        let rwt = Rwt::with_payload(Payload { exp: 13 }, "secret").unwrap();
+        assert!(rwt.check_expiry(13).is_ok());
+        match rwt.check_expiry(14) {
+            Err(Error::Expired) => (),
+            other => panic!("expected Expired, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn is_usable_checks_signature_and_expiry() {
+        let rwt = Rwt::with_payload(Payload { exp: 13 }, "secret").unwrap();
+        assert!(rwt.is_usable("secret", 13).is_ok());
+        match rwt.is_usable("secret", 14) {
+            Err(Error::Expired) => (),
+            other => panic!("expected Expired, got {:?}", other),
+        }
+        match rwt.is_usable("other secret", 13) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+        match rwt.is_usable("other secret", 14) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn is_usable_window_checks_both_ends() {
+        let rwt = Rwt::with_payload(Window { nbf: 10, exp: 20 }, "secret").unwrap();
+        assert!(rwt.is_usable_window("secret", 10, 0).is_ok());
+        assert!(rwt.is_usable_window("secret", 20, 0).is_ok());
+        assert!(rwt.is_usable_window("secret", 8, 2).is_ok());
+        match rwt.is_usable_window("secret", 9, 0) {
+            Err(Error::NotYetValid) => (),
+            other => panic!("expected NotYetValid, got {:?}", other),
+        }
+        match rwt.is_usable_window("secret", FixedClock(21), 0) {
+            Err(Error::Expired) => (),
+            other => panic!("expected Expired, got {:?}", other),
+        }
+        match rwt.is_usable_window("other secret", 9, 0) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn by_expiry_sorts_soonest_first() {
+        let mut tokens: Vec<_> = [20, 10, 30]
+            .iter()
+            .map(|&exp| ByExpiry(Rwt::with_payload(Payload { exp }, "secret").unwrap()))
+            .collect();
+        tokens.sort();
+
+        let order: Vec<_> = tokens.iter().map(|token| token.0.payload.exp).collect();
+        assert_eq!(vec![10, 20, 30], order);
+    }
+
+    #[test]
+    fn by_expiry_heap_pops_soonest() {
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+
+        let mut heap = BinaryHeap::new();
+        for &exp in &[20, 10, 30] {
+            let rwt = Rwt::with_payload(Payload { exp }, "secret").unwrap();
+            heap.push(Reverse(ByExpiry(rwt)));
+        }
+        let Reverse(ByExpiry(soonest)) = heap.pop().unwrap();
+        assert_eq!(10, soonest.payload.exp);
+    }
+
+    #[test]
+    fn is_expired_with_fixed_clock() {
+        let rwt = Rwt::with_payload(Payload { exp: 13 }, "secret").unwrap();
+        assert!(!rwt.is_expired(FixedClock(13)));
+        assert!(rwt.is_expired(FixedClock(14)));
+        assert!(rwt.check_expiry(FixedClock(14)).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn system_clock_is_after_2020() {
+        use super::{Clock, SystemClock};
+
+        assert!(SystemClock.now() > 1_577_836_800);
+        let rwt = Rwt::with_payload(Payload { exp: 1_577_836_800 }, "secret").unwrap();
+        assert!(rwt.is_expired(SystemClock));
+    }
+
+    #[test]
+    fn is_active_with_fixed_clock() {
+        let rwt = Rwt::with_payload(Scheduled { nbf: 100 }, "secret").unwrap();
+        let clock = FixedClock(95);
+        assert!(rwt.is_active(clock, 5));
+        assert!(!rwt.is_active(clock, 4));
+    }
+
+    #[test]
+    fn is_active_at_nbf() {
+        let rwt = Rwt::with_payload(Scheduled { nbf: 100 }, "secret").unwrap();
+        assert!(rwt.is_active(100, 0));
+        assert!(rwt.is_active(101, 0));
+    }
+
+    #[test]
+    fn is_not_active_before_nbf() {
+        let rwt = Rwt::with_payload(Scheduled { nbf: 100 }, "secret").unwrap();
+        assert!(!rwt.is_active(99, 0));
+    }
+
+    #[test]
+    fn is_active_within_leeway() {
+        let rwt = Rwt::with_payload(Scheduled { nbf: 100 }, "secret").unwrap();
+        assert!(rwt.is_active(95, 5));
+        assert!(!rwt.is_active(94, 5));
+    }
+
+    #[test]
+    fn check_active_yields_not_yet_valid() {
+        let rwt = Rwt::with_payload(Scheduled { nbf: 100 }, "secret").unwrap();
+        assert!(rwt.check_active(95, 5).is_ok());
+        match rwt.check_active(94, 5) {
+            Err(Error::NotYetValid) => (),
+            other => panic!("expected NotYetValid, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fresh_rwt_is_not_older_than_max_age() {
+        let rwt = Rwt::with_payload(Issued { iat: 100 }, "secret").unwrap();
+        assert!(!rwt.older_than(100, 60));
+        assert!(!rwt.older_than(160, 60));
+    }
+
+    #[test]
+    fn aged_rwt_is_older_than_max_age() {
+        let rwt = Rwt::with_payload(Issued { iat: 100 }, "secret").unwrap();
+        assert!(rwt.older_than(161, 60));
+    }
+
+    #[test]
+    fn older_than_with_fixed_clock() {
+        let rwt = Rwt::with_payload(Issued { iat: 100 }, "secret").unwrap();
+        assert!(!rwt.older_than(FixedClock(160), 60));
+        assert!(rwt.older_than(FixedClock(161), 60));
+    }
+
+    #[test]
+    fn future_rwt_is_not_older_than_anything() {
+        let rwt = Rwt::with_payload(Issued { iat: 100 }, "secret").unwrap();
+        assert!(!rwt.older_than(50, 0));
+        assert!(!rwt.older_than(50, -100));
+    }
+
+    #[test]
+    fn refresh_restamps_and_resigns() {
+        let old = Rwt::with_payload(Payload { exp: 13 }, "secret").unwrap();
+        let new = old.clone().refresh("secret", 42).unwrap();
+
+        assert_eq!(42, new.payload.exp);
+        assert!(new.is_valid("secret"));
+        assert_ne!(old.signature(), new.signature());
+
+        let replayed = Rwt {
+            signature: old.signature().into(),
+            ..new
+        };
+        assert!(!replayed.is_valid("secret"));
+    }
+}
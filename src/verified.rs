@@ -0,0 +1,104 @@
+use crate::{Algorithm, Result, Rwt};
+use alloc::string::String;
+use core::fmt::Debug;
+use core::str::FromStr;
+
+/// A payload whose signature has been checked, along with what signed it.
+///
+/// The only way to get one is `Rwt::verified_parts`, so anything holding a `VerifiedToken` can
+/// trust the algorithm and key id as much as the payload, which is not true of an `Rwt` that
+/// might never have been verified at all.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct VerifiedToken<T> {
+    payload: T,
+    algorithm: Algorithm,
+    key_id: Option<String>,
+}
+
+impl<T> VerifiedToken<T> {
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
+
+    /// The algorithm the token was signed with.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// The id of the key the token was signed with, if it named one.
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    pub fn into_payload(self) -> T {
+        self.payload
+    }
+}
+
+impl<T, E> Rwt<T>
+where
+    E: Debug,
+    T: FromStr<Err = E>,
+{
+    /// Parse and verify a token, handing back the payload and the details of its signature.
+    ///
+    /// Errors are those of `parse_verified`, which this is, apart from the return type.
+    pub fn verified_parts<S: AsRef<[u8]>>(token: &str, secret: S) -> Result<VerifiedToken<T>> {
+        let rwt = Rwt::parse_verified(token, secret)?;
+        Ok(VerifiedToken {
+            payload: rwt.payload,
+            algorithm: rwt.header.alg,
+            key_id: rwt.header.kid,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{Algorithm, Error, Rwt};
+    use serde::{Deserialize, Serialize};
+    use std::str::FromStr;
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        sub: String,
+    }
+
+    impl FromStr for Payload {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    fn payload() -> Payload {
+        Payload {
+            sub: "alice".into(),
+        }
+    }
+
+    #[test]
+    fn verified_parts_match_signer() {
+        let token = Rwt::with_payload_kid(payload(), "secret", "k1")
+            .unwrap()
+            .encode()
+            .unwrap();
+        let verified = Rwt::<Payload>::verified_parts(&token, "secret").unwrap();
+        assert_eq!(&payload(), verified.payload());
+        assert_eq!(Algorithm::HmacSha256, verified.algorithm());
+        assert_eq!(Some("k1"), verified.key_id());
+    }
+
+    #[test]
+    fn verified_parts_rejects_bad_secret() {
+        let token = Rwt::with_payload(payload(), "secret")
+            .unwrap()
+            .encode()
+            .unwrap();
+        match Rwt::<Payload>::verified_parts(&token, "other secret") {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+}